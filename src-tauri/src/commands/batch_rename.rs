@@ -1,14 +1,14 @@
-//! Batch rename image files (and their .txt caption files) with a prefix and sequential index.
+//! Batch rename image files (and their caption sidecars) with a prefix and sequential index.
 
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::collections::HashMap;
 use tauri::Emitter;
 
-fn caption_path_for(image_path: &Path) -> PathBuf {
-    image_path.with_extension("txt")
-}
+use super::error::CommandError;
+use super::ratings::rename_rating_keys;
+use super::sidecar;
 
 #[derive(Debug, Deserialize)]
 pub struct BatchRenamePayload {
@@ -37,12 +37,12 @@ pub struct BatchRenameProgress {
     pub current_file: String,
 }
 
-fn load_json_map(path: &Path) -> Result<HashMap<String, String>, String> {
+fn load_json_map(path: &Path) -> Result<HashMap<String, String>, CommandError> {
     if !path.exists() {
         return Ok(HashMap::new());
     }
-    let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
-    let data: serde_json::Value = serde_json::from_str(&content).map_err(|e| e.to_string())?;
+    let content = fs::read_to_string(path)?;
+    let data: serde_json::Value = serde_json::from_str(&content)?;
     if let Some(obj) = data.as_object() {
         if let Some(map_val) = obj.get("ratings").or_else(|| obj.get("statuses")) {
             if let Some(map) = map_val.as_object() {
@@ -59,19 +59,25 @@ fn load_json_map(path: &Path) -> Result<HashMap<String, String>, String> {
     Ok(HashMap::new())
 }
 
-fn save_json_map(path: &Path, map: &HashMap<String, String>, key: &str) -> Result<(), String> {
-    let parent = path.parent().ok_or("No parent directory")?;
-    fs::create_dir_all(parent).map_err(|e| e.to_string())?;
-    
+fn save_json_map(
+    path: &Path,
+    map: &HashMap<String, String>,
+    key: &str,
+) -> Result<(), CommandError> {
+    let parent = path
+        .parent()
+        .ok_or_else(|| CommandError::invalid("No parent directory"))?;
+    fs::create_dir_all(parent)?;
+
     let mut obj = serde_json::Map::new();
     let mut inner = serde_json::Map::new();
     for (k, v) in map {
         inner.insert(k.clone(), serde_json::Value::String(v.clone()));
     }
     obj.insert(key.to_string(), serde_json::Value::Object(inner));
-    
-    let content = serde_json::to_string_pretty(&obj).map_err(|e| e.to_string())?;
-    fs::write(path, content).map_err(|e| e.to_string())
+
+    let content = serde_json::to_string_pretty(&obj)?;
+    Ok(fs::write(path, content)?)
 }
 
 /// Renames image files and their caption files with prefix + zero-padded index.
@@ -81,39 +87,39 @@ fn save_json_map(path: &Path, map: &HashMap<String, String>, key: &str) -> Resul
 pub fn batch_rename(
     payload: BatchRenamePayload,
     window: tauri::Window,
-) -> Result<BatchRenameResult, String> {
+) -> Result<BatchRenameResult, CommandError> {
     let root = PathBuf::from(&payload.root_path);
     if !root.exists() || !root.is_dir() {
-        return Err("Root path does not exist or is not a directory".to_string());
+        return Err(CommandError::NotFound(
+            "Root path does not exist or is not a directory".to_string(),
+        ));
     }
 
-    let canonical_root = root.canonicalize().map_err(|e| e.to_string())?;
+    let canonical_root = root.canonicalize()?;
 
     let prefix = payload.prefix.trim();
     if prefix.is_empty() {
-        return Err("Prefix cannot be empty".to_string());
+        return Err(CommandError::invalid("Prefix cannot be empty"));
     }
 
     let zero_pad = payload.zero_pad.max(1).min(12);
     let mut index = payload.start_index;
     let mut errors = Vec::new();
     let mut renamed = 0u32;
-    
+
     // Load ratings and crop status files
-    let ratings_path = root.join(".lora-studio").join("ratings.json");
     let crop_status_path = root.join(".lora-studio").join("crop_status.json");
-    let mut ratings = load_json_map(&ratings_path).unwrap_or_default();
     let mut crop_statuses = load_json_map(&crop_status_path).unwrap_or_default();
-    
+
     // Track path mappings for updating metadata
     let mut path_mappings: Vec<(String, String)> = Vec::new();
-    
+
     let total = payload.relative_paths.len() as u32;
     let mut current = 0u32;
 
     for relative_path in &payload.relative_paths {
         current += 1;
-        
+
         // Emit progress event
         let _ = window.emit(
             "batch-rename-progress",
@@ -152,7 +158,13 @@ pub fn batch_rename(
             .and_then(|e| e.to_str())
             .unwrap_or("png")
             .to_string();
-        let new_name = format!("{}_{:0width$}.{}", prefix, index, ext, width = zero_pad as usize);
+        let new_name = format!(
+            "{}_{:0width$}.{}",
+            prefix,
+            index,
+            ext,
+            width = zero_pad as usize
+        );
         let parent = old_path.parent().unwrap_or(&root);
         let new_path = parent.join(&new_name);
 
@@ -174,48 +186,50 @@ pub fn batch_rename(
             continue;
         }
 
-        let caption_old = caption_path_for(&old_path);
-        let caption_new = new_path.with_extension("txt");
+        // Every sidecar moves with the image (e.g. .txt and .json), or none do.
+        let captions: Vec<(PathBuf, PathBuf)> = sidecar::existing_all(&old_path)
+            .into_iter()
+            .map(|old| {
+                let new = new_path.with_extension(old.extension().unwrap_or_default());
+                (old, new)
+            })
+            .collect();
         let mut ok = true;
-        if caption_old.exists() {
-            if caption_new.exists() {
-                let _ = fs::rename(&new_path, &old_path);
-                errors.push(format!("Caption target exists: {}", new_name));
-                ok = false;
-            } else if fs::rename(&caption_old, &caption_new).is_err() {
-                let _ = fs::rename(&new_path, &old_path);
-                errors.push(format!("Failed to rename caption for: {}", relative_path));
-                ok = false;
+        if captions.iter().any(|(_, new)| new.exists()) {
+            let _ = fs::rename(&new_path, &old_path);
+            errors.push(format!("Caption target exists: {}", new_name));
+            ok = false;
+        } else {
+            for (i, (old, new)) in captions.iter().enumerate() {
+                if fs::rename(old, new).is_err() {
+                    for (old, new) in &captions[..i] {
+                        let _ = fs::rename(new, old);
+                    }
+                    let _ = fs::rename(&new_path, &old_path);
+                    errors.push(format!("Failed to rename caption for: {}", relative_path));
+                    ok = false;
+                    break;
+                }
             }
         }
         if ok {
             renamed += 1;
             // Track the path mapping for metadata updates
-            let new_relative = new_path.strip_prefix(&root)
+            let new_relative = new_path
+                .strip_prefix(&root)
                 .map(|p| p.to_string_lossy().replace('\\', "/"))
                 .unwrap_or_else(|_| new_name.clone());
             path_mappings.push((relative_path.clone(), new_relative));
         }
         index += 1;
     }
-    
+
     // Update ratings file with new paths
     if !path_mappings.is_empty() {
-        let mut updated_ratings = HashMap::new();
-        for (old_path, new_path) in &path_mappings {
-            if let Some(rating) = ratings.remove(old_path) {
-                updated_ratings.insert(new_path.clone(), rating);
-            }
-        }
-        // Keep any ratings for files that weren't renamed
-        for (k, v) in ratings {
-            updated_ratings.insert(k, v);
-        }
-        
-        if let Err(e) = save_json_map(&ratings_path, &updated_ratings, "ratings") {
+        if let Err(e) = rename_rating_keys(&payload.root_path, &path_mappings) {
             eprintln!("Warning: Failed to update ratings file: {}", e);
         }
-        
+
         // Update crop_status file with new paths
         let mut updated_crop_statuses = HashMap::new();
         for (old_path, new_path) in &path_mappings {
@@ -227,7 +241,7 @@ pub fn batch_rename(
         for (k, v) in crop_statuses {
             updated_crop_statuses.insert(k, v);
         }
-        
+
         if let Err(e) = save_json_map(&crop_status_path, &updated_crop_statuses, "statuses") {
             eprintln!("Warning: Failed to update crop_status file: {}", e);
         }
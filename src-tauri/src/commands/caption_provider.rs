@@ -0,0 +1,93 @@
+//! Single caption entry point across providers, so the frontend doesn't branch per provider.
+//! Ollama and LM Studio both speak the OpenAI-compatible chat API, so both dispatch to the
+//! lm_studio implementation with the provider's base URL.
+
+use serde::Deserialize;
+
+use super::error::CommandError;
+use super::lm_studio::{self, CaptionResult};
+use super::ollama::{api_root, DEFAULT_OLLAMA_BASE_URL};
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptionProvider {
+    Ollama,
+    LmStudio,
+}
+
+impl CaptionProvider {
+    fn default_base_url(self) -> &'static str {
+        match self {
+            Self::Ollama => DEFAULT_OLLAMA_BASE_URL,
+            Self::LmStudio => lm_studio::DEFAULT_BASE_URL,
+        }
+    }
+}
+
+/// Settings shared by the network providers. Unset fields fall back to the provider defaults.
+#[derive(Debug, Default, Deserialize)]
+pub struct ProviderSettings {
+    #[serde(default)]
+    pub base_url: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
+    #[serde(default)]
+    pub timeout_secs: Option<u32>,
+    #[serde(default)]
+    pub max_image_dimension: Option<u32>,
+    #[serde(default)]
+    pub api_key: Option<String>,
+    #[serde(default)]
+    pub headers: Option<Vec<(String, String)>>,
+    #[serde(default)]
+    pub max_retries: Option<u32>,
+    #[serde(default)]
+    pub retry_delay_ms: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GenerateCaptionPayload {
+    pub provider: CaptionProvider,
+    pub image_path: String,
+    pub prompt: String,
+    #[serde(default)]
+    pub settings: ProviderSettings,
+    /// Caption only this rectangle of the image (cropped in memory).
+    #[serde(default)]
+    pub region: Option<lm_studio::CaptionRegion>,
+}
+
+/// Generate a caption for one image with the chosen provider.
+#[tauri::command]
+pub async fn generate_caption(
+    payload: GenerateCaptionPayload,
+) -> Result<CaptionResult, CommandError> {
+    let settings = payload.settings;
+    let base_url = settings
+        .base_url
+        .filter(|u| !u.trim().is_empty())
+        .unwrap_or_else(|| payload.provider.default_base_url().to_string());
+
+    lm_studio::generate_caption_lm_studio(lm_studio::GenerateCaptionPayload {
+        image_path: payload.image_path,
+        // Chat completions are requested at `{base}/v1/chat/completions`.
+        base_url: api_root(&base_url),
+        model: settings.model,
+        prompt: payload.prompt,
+        max_tokens: settings
+            .max_tokens
+            .unwrap_or_else(lm_studio::default_max_tokens),
+        timeout_secs: settings
+            .timeout_secs
+            .unwrap_or_else(lm_studio::default_timeout_secs),
+        max_image_dimension: settings.max_image_dimension,
+        api_key: settings.api_key,
+        headers: settings.headers,
+        max_retries: settings.max_retries,
+        retry_delay_ms: settings.retry_delay_ms,
+        region: payload.region,
+    })
+    .await
+}
@@ -1,14 +1,85 @@
+use instant_clip_tokenizer::Tokenizer;
+use once_cell::sync::Lazy;
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
-use walkdir::WalkDir;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use tauri::{AppHandle, Emitter};
 
-/// Get the caption file path for an image (same name, .txt extension).
-fn caption_path_for(image_path: &str) -> PathBuf {
-    let path = PathBuf::from(image_path);
-    path.with_extension("txt")
+use super::error::CommandError;
+use super::sidecar::{self, CaptionExt};
+use super::walk::{ProjectWalk, WalkedImages};
+
+/// Get the caption sidecar for an image: whichever exists, else a new `preferred` one.
+fn caption_path_for(image_path: &str, preferred: CaptionExt) -> PathBuf {
+    sidecar::resolve(Path::new(image_path), preferred)
+}
+
+const HISTORY_DIR_NAME: &str = ".caption_history";
+const HISTORY_LIMIT: usize = 50;
+
+/// Per-caption undo/redo snapshots (raw file content), stored next to the images.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CaptionHistory {
+    undo: Vec<String>,
+    redo: Vec<String>,
+}
+
+/// Journal file for a caption: `<dir>/.caption_history/<stem>.json`. Keyed by the caption stem
+/// since images with the same stem share one .txt.
+fn history_path_for(caption_path: &Path) -> PathBuf {
+    let parent = caption_path.parent().unwrap_or(Path::new("."));
+    let stem = caption_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("caption");
+    parent.join(HISTORY_DIR_NAME).join(format!("{}.json", stem))
+}
+
+fn load_history(caption_path: &Path) -> CaptionHistory {
+    fs::read_to_string(history_path_for(caption_path))
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+fn save_history(caption_path: &Path, history: &CaptionHistory) -> Result<(), CommandError> {
+    let path = history_path_for(caption_path);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let json = serde_json::to_string(history)?;
+    Ok(fs::write(path, json)?)
+}
+
+/// sidecar::write with the file named in the error, for project-wide rewrites.
+fn write_named(caption_path: &Path, content: &str) -> Result<(), CommandError> {
+    sidecar::write(caption_path, content).map_err(|e| {
+        CommandError::from(e).context(format!("Failed to write {}", caption_path.display()))
+    })
+}
+
+fn read_raw(caption_path: &Path) -> String {
+    sidecar::read(caption_path).unwrap_or_default()
+}
+
+/// Write a caption edit, journaling the previous content for undo. A new edit clears redo.
+/// Journaling is best-effort so a read-only history folder never blocks saving the caption.
+fn write_with_history(caption_path: &Path, content: &str) -> Result<(), CommandError> {
+    let previous = read_raw(caption_path);
+    sidecar::write(caption_path, content)?;
+    if previous != content {
+        let mut history = load_history(caption_path);
+        history.undo.push(previous);
+        if history.undo.len() > HISTORY_LIMIT {
+            history.undo.remove(0);
+        }
+        history.redo.clear();
+        let _ = save_history(caption_path, &history);
+    }
+    Ok(())
 }
 
 #[derive(Debug, Deserialize)]
@@ -21,49 +92,232 @@ pub struct CaptionData {
     pub exists: bool,
     pub raw: String,
     pub tags: Vec<String>,
+    pub char_count: usize,
+    pub word_count: usize,
+    /// Parsed tag count; a prose caption counts as one tag.
+    pub tag_count: usize,
+    pub longest_tag: Option<String>,
+}
+
+impl CaptionData {
+    fn missing() -> Self {
+        Self::from_raw(false, "")
+    }
+
+    fn from_raw(exists: bool, raw: &str) -> Self {
+        let raw = raw.trim();
+        let tags = parse_tags(raw);
+        // Reversed so ties resolve to the first tag.
+        let longest_tag = tags.iter().rev().max_by_key(|t| t.chars().count()).cloned();
+        CaptionData {
+            exists,
+            raw: raw.to_string(),
+            char_count: raw.chars().count(),
+            word_count: raw.split_whitespace().count(),
+            tag_count: tags.len(),
+            longest_tag,
+            tags,
+        }
+    }
 }
 
 /// Reads the caption file for an image. Returns tags parsed from comma-separated format.
 #[tauri::command]
-pub fn read_caption(payload: ReadCaptionPayload) -> Result<CaptionData, String> {
-    let caption_path = caption_path_for(&payload.path);
+pub fn read_caption(payload: ReadCaptionPayload) -> Result<CaptionData, CommandError> {
+    let caption_path = caption_path_for(&payload.path, CaptionExt::Txt);
 
     if !caption_path.exists() {
-        return Ok(CaptionData {
-            exists: false,
-            raw: String::new(),
-            tags: Vec::new(),
-        });
+        return Ok(CaptionData::missing());
     }
 
-    let raw = fs::read_to_string(&caption_path).map_err(|e| e.to_string())?;
-    let tags = parse_tags(&raw);
+    let raw = sidecar::read(&caption_path)?;
+    Ok(CaptionData::from_raw(true, &raw))
+}
+
+#[derive(Debug, Serialize)]
+pub struct RawCaption {
+    pub exists: bool,
+    pub raw: String,
+    pub byte_len: usize,
+}
 
-    Ok(CaptionData {
+/// The image's .txt caption exactly as stored: no trimming or tag parsing, so whitespace and line
+/// breaks round-trip. Only the .txt sidecar is read; an image with just a .caption or .json
+/// reports exists false. Use read_caption for the tag editor.
+#[tauri::command]
+pub fn get_caption_raw(payload: ReadCaptionPayload) -> Result<RawCaption, CommandError> {
+    let caption_path = Path::new(&payload.path).with_extension(CaptionExt::Txt.extension());
+    let raw = match fs::read_to_string(&caption_path) {
+        Ok(raw) => raw,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(RawCaption {
+                exists: false,
+                raw: String::new(),
+                byte_len: 0,
+            })
+        }
+        Err(e) => return Err(e.into()),
+    };
+    Ok(RawCaption {
         exists: true,
-        raw: raw.trim().to_string(),
-        tags,
+        byte_len: raw.len(),
+        raw,
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CaptionChangedSincePayload {
+    pub path: String,
+    /// Caption text as the editor loaded it.
+    pub known_raw: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CaptionChangedSince {
+    pub changed: bool,
+    pub current_raw: String,
+}
+
+/// Optimistic-concurrency check before saving: has the caption on disk changed since the editor
+/// loaded `known_raw`? A missing file reads as empty. Comparison ignores surrounding whitespace.
+#[tauri::command]
+pub fn caption_changed_since(
+    payload: CaptionChangedSincePayload,
+) -> Result<CaptionChangedSince, CommandError> {
+    let current_raw = match sidecar::read(&caption_path_for(&payload.path, CaptionExt::Txt)) {
+        Ok(raw) => raw.trim().to_string(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(CaptionChangedSince {
+        changed: current_raw != payload.known_raw.trim(),
+        current_raw,
     })
 }
 
+#[derive(Debug, Serialize)]
+pub struct CaptionWritable {
+    pub writable: bool,
+    /// Why not, when writable is false.
+    pub reason: Option<String>,
+}
+
+impl CaptionWritable {
+    fn no(reason: impl Into<String>) -> Self {
+        Self {
+            writable: false,
+            reason: Some(reason.into()),
+        }
+    }
+}
+
+/// Whether saving this image's caption would succeed, so the editor can be disabled up front.
+/// Saves write a temp file next to the caption and rename it over, so the folder must accept new
+/// files and an existing caption must not be read-only or locked.
+#[tauri::command]
+pub fn can_write_caption(payload: ReadCaptionPayload) -> Result<CaptionWritable, CommandError> {
+    let caption_path = caption_path_for(&payload.path, CaptionExt::Txt);
+    let Some(dir) = caption_path.parent().filter(|d| d.is_dir()) else {
+        return Ok(CaptionWritable::no("Folder does not exist"));
+    };
+    if let Ok(meta) = fs::metadata(&caption_path) {
+        if meta.permissions().readonly() {
+            return Ok(CaptionWritable::no("Caption file is read-only"));
+        }
+        // Opening for append changes nothing but fails on files locked by another process.
+        if let Err(e) = fs::OpenOptions::new().append(true).open(&caption_path) {
+            return Ok(CaptionWritable::no(format!(
+                "Caption file can't be opened: {}",
+                e
+            )));
+        }
+    }
+    let name = caption_path
+        .file_name()
+        .map(|n| n.to_string_lossy())
+        .unwrap_or_default();
+    let probe = dir.join(format!(".{}.probe", name));
+    match fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)
+    {
+        Ok(_) => {
+            let _ = fs::remove_file(&probe);
+            Ok(CaptionWritable {
+                writable: true,
+                reason: None,
+            })
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Ok(CaptionWritable {
+            writable: true,
+            reason: None,
+        }),
+        Err(e) => Ok(CaptionWritable::no(format!(
+            "Folder is not writable: {}",
+            e
+        ))),
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct WriteCaptionPayload {
     pub path: String,
     pub tags: Vec<String>,
+    /// Tags to drop before writing (case-insensitive, whole-tag match).
+    #[serde(default)]
+    pub blacklist: Option<Vec<String>>,
+    /// Sidecar to create when the image has none yet.
+    #[serde(default)]
+    pub caption_ext: CaptionExt,
+    /// Text between tags (default ", "); see tag_separator.
+    #[serde(default)]
+    pub separator: Option<String>,
+}
+
+pub const DEFAULT_SEPARATOR: &str = ", ";
+
+/// The separator to join tags with. It must contain a comma or a newline, the characters
+/// parse_tags splits on, so what is written reads back as the same tags.
+pub fn tag_separator(separator: Option<&str>) -> Result<&str, CommandError> {
+    match separator {
+        None => Ok(DEFAULT_SEPARATOR),
+        Some("") => Err(CommandError::invalid("Separator cannot be empty")),
+        Some(sep) if !sep.contains([',', '\n']) => Err(CommandError::invalid(
+            "Separator must contain a comma or a newline",
+        )),
+        Some(sep) => Ok(sep),
+    }
 }
 
-/// Writes tags to the caption file for an image (comma-separated).
+/// Writes tags to the caption file for an image (comma-separated unless `separator` is set).
 #[tauri::command]
-pub fn write_caption(payload: WriteCaptionPayload) -> Result<(), String> {
-    let caption_path = caption_path_for(&payload.path);
-    let content = payload.tags.join(", ");
-    fs::write(&caption_path, &content).map_err(|e| e.to_string())?;
-    Ok(())
+pub fn write_caption(payload: WriteCaptionPayload) -> Result<(), CommandError> {
+    let separator = tag_separator(payload.separator.as_deref())?;
+    let caption_path = caption_path_for(&payload.path, payload.caption_ext);
+    let mut tags = payload.tags;
+    if let Some(blacklist) = &payload.blacklist {
+        strip_blacklisted(&mut tags, blacklist);
+    }
+    let content = tags.join(separator);
+    write_with_history(&caption_path, &content)
+}
+
+/// Remove tags matching any blacklist entry (case-insensitive, whole tag). Returns how many were removed.
+fn strip_blacklisted(tags: &mut Vec<String>, blacklist: &[String]) -> usize {
+    let blocked: HashSet<String> = blacklist
+        .iter()
+        .map(|t| t.trim().to_lowercase())
+        .filter(|t| !t.is_empty())
+        .collect();
+    let before = tags.len();
+    tags.retain(|t| !blocked.contains(&t.trim().to_lowercase()));
+    before - tags.len()
 }
 
-/// Parse comma-separated tags from raw caption text.
-fn parse_tags(raw: &str) -> Vec<String> {
-    raw.split(',')
+/// Parse tags from raw caption text, separated by commas or newlines.
+pub fn parse_tags(raw: &str) -> Vec<String> {
+    raw.split([',', '\n'])
         .map(|s| s.trim().to_string())
         .filter(|s| !s.is_empty())
         .collect()
@@ -73,24 +327,65 @@ fn parse_tags(raw: &str) -> Vec<String> {
 pub struct AddTagPayload {
     pub path: String,
     pub tag: String,
+    /// Sidecar to create when the image has none yet.
+    #[serde(default)]
+    pub caption_ext: CaptionExt,
+    #[serde(default)]
+    pub canonical_case: CanonicalCase,
+    /// Text between tags (default ", "); see tag_separator.
+    #[serde(default)]
+    pub separator: Option<String>,
+}
+
+/// Casing applied when adding a tag.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CanonicalCase {
+    /// Keep the tag as typed; an existing tag differing only in case is left alone.
+    #[default]
+    Preserve,
+    /// Lowercase the new tag and rewrite existing case variants of it to lowercase.
+    Lowercase,
+}
+
+/// Unicode-aware key for comparing tags regardless of case ("Café" == "café").
+fn fold_tag(tag: &str) -> String {
+    tag.trim().to_lowercase()
 }
 
-/// Adds a tag to the caption file if not already present.
+/// Adds a tag to the caption file if not already present (compared case-insensitively). With
+/// `canonical_case: lowercase`, existing case variants are rewritten instead of skipped.
 #[tauri::command]
-pub fn add_tag(payload: AddTagPayload) -> Result<Vec<String>, String> {
-    let caption_path = caption_path_for(&payload.path);
+pub fn add_tag(payload: AddTagPayload) -> Result<Vec<String>, CommandError> {
+    let separator = tag_separator(payload.separator.as_deref())?;
+    let caption_path = caption_path_for(&payload.path, payload.caption_ext);
     let mut tags = if caption_path.exists() {
-        let raw = fs::read_to_string(&caption_path).map_err(|e| e.to_string())?;
+        let raw = sidecar::read(&caption_path)?;
         parse_tags(&raw)
     } else {
         Vec::new()
     };
 
-    let tag = payload.tag.trim().to_string();
-    if !tag.is_empty() && !tags.iter().any(|t| t.eq_ignore_ascii_case(&tag)) {
-        tags.push(tag);
-        let content = tags.join(", ");
-        fs::write(&caption_path, &content).map_err(|e| e.to_string())?;
+    let tag = match payload.canonical_case {
+        CanonicalCase::Preserve => payload.tag.trim().to_string(),
+        CanonicalCase::Lowercase => payload.tag.trim().to_lowercase(),
+    };
+    if tag.is_empty() {
+        return Ok(tags);
+    }
+    let key = fold_tag(&tag);
+    let before = tags.clone();
+    match tags.iter().position(|t| fold_tag(t) == key) {
+        None => tags.push(tag),
+        Some(first) if matches!(payload.canonical_case, CanonicalCase::Lowercase) => {
+            tags[first] = tag;
+            let mut kept = false;
+            tags.retain(|t| fold_tag(t) != key || !std::mem::replace(&mut kept, true));
+        }
+        Some(_) => {}
+    }
+    if tags != before {
+        write_with_history(&caption_path, &tags.join(separator))?;
     }
 
     Ok(tags)
@@ -100,23 +395,27 @@ pub fn add_tag(payload: AddTagPayload) -> Result<Vec<String>, String> {
 pub struct RemoveTagPayload {
     pub path: String,
     pub tag: String,
+    /// Text between tags (default ", "); see tag_separator.
+    #[serde(default)]
+    pub separator: Option<String>,
 }
 
 /// Removes a tag from the caption file.
 #[tauri::command]
-pub fn remove_tag(payload: RemoveTagPayload) -> Result<Vec<String>, String> {
-    let caption_path = caption_path_for(&payload.path);
+pub fn remove_tag(payload: RemoveTagPayload) -> Result<Vec<String>, CommandError> {
+    let separator = tag_separator(payload.separator.as_deref())?;
+    let caption_path = caption_path_for(&payload.path, CaptionExt::Txt);
     if !caption_path.exists() {
         return Ok(Vec::new());
     }
 
-    let raw = fs::read_to_string(&caption_path).map_err(|e| e.to_string())?;
+    let raw = sidecar::read(&caption_path)?;
     let mut tags = parse_tags(&raw);
     let tag_lower = payload.tag.trim().to_lowercase();
     tags.retain(|t| t.to_lowercase() != tag_lower);
 
-    let content = tags.join(", ");
-    fs::write(&caption_path, &content).map_err(|e| e.to_string())?;
+    let content = tags.join(separator);
+    write_with_history(&caption_path, &content)?;
 
     Ok(tags)
 }
@@ -125,65 +424,800 @@ pub fn remove_tag(payload: RemoveTagPayload) -> Result<Vec<String>, String> {
 pub struct ReorderTagsPayload {
     pub path: String,
     pub tags: Vec<String>,
+    /// Sidecar to create when the image has none yet.
+    #[serde(default)]
+    pub caption_ext: CaptionExt,
+    /// Text between tags (default ", "); see tag_separator.
+    #[serde(default)]
+    pub separator: Option<String>,
 }
 
 /// Replaces all tags with the given ordered list.
 #[tauri::command]
-pub fn reorder_tags(payload: ReorderTagsPayload) -> Result<(), String> {
-    let caption_path = caption_path_for(&payload.path);
-    let content = payload.tags.join(", ");
-    fs::write(&caption_path, &content).map_err(|e| e.to_string())?;
-    Ok(())
+pub fn reorder_tags(payload: ReorderTagsPayload) -> Result<(), CommandError> {
+    let separator = tag_separator(payload.separator.as_deref())?;
+    let caption_path = caption_path_for(&payload.path, payload.caption_ext);
+    let content = payload.tags.join(separator);
+    write_with_history(&caption_path, &content)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApplyTemplatePayload {
+    pub paths: Vec<String>,
+    /// e.g. "{trigger}, {subject}, {existing}, high quality"
+    pub template: String,
+    #[serde(default)]
+    pub values: HashMap<String, String>,
+    #[serde(default)]
+    pub caption_ext: CaptionExt,
+    /// Text between tags (default ", "); see tag_separator.
+    #[serde(default)]
+    pub separator: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TemplateResult {
+    pub path: String,
+    pub success: bool,
+    pub tags: Vec<String>,
+    pub error: Option<String>,
 }
 
-const IMAGE_EXT: &[&str] = &["png", "jpg", "jpeg", "webp", "gif", "bmp"];
+/// Replace `{key}` placeholders; `{existing}` is the current caption. Unknown keys are left as-is.
+fn fill_template(template: &str, values: &HashMap<String, String>, existing: &str) -> String {
+    let mut out = String::with_capacity(template.len() + existing.len());
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        match after.find('}') {
+            Some(close) => {
+                let key = &after[..close];
+                match key {
+                    "existing" => out.push_str(existing),
+                    _ => match values.get(key) {
+                        Some(v) => out.push_str(v),
+                        None => out.push_str(&rest[open..open + close + 2]),
+                    },
+                }
+                rest = &after[close + 1..];
+            }
+            None => {
+                out.push_str(&rest[open..]);
+                rest = "";
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Write a caption built from a template to each image. Empty placeholders collapse instead of
+/// leaving dangling commas.
+#[tauri::command]
+pub fn apply_template(payload: ApplyTemplatePayload) -> Result<Vec<TemplateResult>, CommandError> {
+    let separator = tag_separator(payload.separator.as_deref())?;
+    Ok(payload
+        .paths
+        .iter()
+        .map(|path| {
+            let caption_path = caption_path_for(path, payload.caption_ext);
+            let existing = read_raw(&caption_path);
+            let filled = fill_template(&payload.template, &payload.values, existing.trim());
+            let tags = parse_tags(&filled);
+            match write_with_history(&caption_path, &tags.join(separator)) {
+                Ok(()) => TemplateResult {
+                    path: path.clone(),
+                    success: true,
+                    tags,
+                    error: None,
+                },
+                Err(e) => TemplateResult {
+                    path: path.clone(),
+                    success: false,
+                    tags: Vec::new(),
+                    error: Some(e.to_string()),
+                },
+            }
+        })
+        .collect())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CaptionHistoryPayload {
+    pub path: String,
+}
+
+/// Move one snapshot between the history stacks, writing it to the caption file.
+fn step_history(image_path: &str, undo: bool) -> Result<Vec<String>, CommandError> {
+    let caption_path = caption_path_for(image_path, CaptionExt::Txt);
+    let mut history = load_history(&caption_path);
+    let (from, to) = if undo {
+        (&mut history.undo, &mut history.redo)
+    } else {
+        (&mut history.redo, &mut history.undo)
+    };
+    let snapshot = from.pop().ok_or_else(|| {
+        CommandError::invalid(format!("Nothing to {}", if undo { "undo" } else { "redo" }))
+    })?;
+    to.push(read_raw(&caption_path));
+    if to.len() > HISTORY_LIMIT {
+        to.remove(0);
+    }
+    sidecar::write(&caption_path, &snapshot)?;
+    save_history(&caption_path, &history)?;
+    Ok(parse_tags(&snapshot))
+}
+
+/// Restore the caption as it was before the last edit. Returns the resulting tags.
+#[tauri::command]
+pub fn undo_caption(payload: CaptionHistoryPayload) -> Result<Vec<String>, CommandError> {
+    step_history(&payload.path, true)
+}
+
+/// Re-apply the last undone caption edit. Returns the resulting tags.
+#[tauri::command]
+pub fn redo_caption(payload: CaptionHistoryPayload) -> Result<Vec<String>, CommandError> {
+    step_history(&payload.path, false)
+}
+
+/// CLIP context length used by SD trainers (start + end markers + 75 content tokens).
+const CLIP_TOKEN_LIMIT: usize = 77;
+
+/// Building the BPE tables is expensive, so share one tokenizer (bundled OpenAI CLIP vocabulary).
+static CLIP_TOKENIZER: Lazy<Tokenizer> = Lazy::new(Tokenizer::new);
+
+#[derive(Debug, Deserialize)]
+pub struct CountTokensPayload {
+    pub path: String,
+    /// Token limit including start/end markers (default 77).
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CountTokensBatchPayload {
+    pub paths: Vec<String>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TokenCount {
+    pub path: String,
+    /// CLIP tokens including the start/end markers, comparable to `limit`.
+    pub token_count: usize,
+    pub limit: usize,
+    pub over_limit: bool,
+    /// When over the limit: the (normalized) text the trainer keeps and the part it cuts.
+    pub kept_text: Option<String>,
+    pub cut_text: Option<String>,
+}
+
+fn count_caption_tokens(image_path: &str, limit: usize) -> Result<TokenCount, CommandError> {
+    let caption_path = caption_path_for(image_path, CaptionExt::Txt);
+    let raw = if caption_path.exists() {
+        sidecar::read(&caption_path)?
+    } else {
+        String::new()
+    };
+
+    let tokenizer = &*CLIP_TOKENIZER;
+    let mut tokens = Vec::new();
+    tokenizer.encode(raw.trim(), &mut tokens);
+    let token_count = tokens.len() + 2;
+    let over_limit = token_count > limit;
+    let (kept_text, cut_text) = if over_limit {
+        let keep = limit.saturating_sub(2);
+        let cut = tokens.split_off(keep);
+        (
+            Some(tokenizer.decode(tokens).trim().to_string()),
+            Some(tokenizer.decode(cut).trim().to_string()),
+        )
+    } else {
+        (None, None)
+    };
 
-fn is_image_path(p: &Path) -> bool {
-    let ext = p
-        .extension()
-        .and_then(|e| e.to_str())
-        .map(|e| e.to_lowercase());
-    ext.as_ref()
-        .map(|e| IMAGE_EXT.iter().any(|x| x.eq_ignore_ascii_case(e)))
-        .unwrap_or(false)
+    Ok(TokenCount {
+        path: image_path.to_string(),
+        token_count,
+        limit,
+        over_limit,
+        kept_text,
+        cut_text,
+    })
+}
+
+/// Count CLIP tokens in an image's caption to flag captions that training would truncate.
+#[tauri::command]
+pub fn count_tokens(payload: CountTokensPayload) -> Result<TokenCount, CommandError> {
+    count_caption_tokens(&payload.path, payload.limit.unwrap_or(CLIP_TOKEN_LIMIT))
+}
+
+#[derive(Debug, Serialize)]
+pub struct TokenCountResult {
+    pub path: String,
+    /// None when the caption couldn't be read.
+    pub tokens: Option<TokenCount>,
+    pub error: Option<String>,
+}
+
+/// Token counts for many images, one entry per path in the same order. An unreadable caption
+/// gets an error instead of being dropped.
+#[tauri::command]
+pub fn count_tokens_batch(
+    payload: CountTokensBatchPayload,
+) -> Result<Vec<TokenCountResult>, CommandError> {
+    let limit = payload.limit.unwrap_or(CLIP_TOKEN_LIMIT);
+    Ok(payload
+        .paths
+        .par_iter()
+        .map(|path| match count_caption_tokens(path, limit) {
+            Ok(tokens) => TokenCountResult {
+                path: path.clone(),
+                tokens: Some(tokens),
+                error: None,
+            },
+            Err(e) => TokenCountResult {
+                path: path.clone(),
+                tokens: None,
+                error: Some(e.to_string()),
+            },
+        })
+        .collect())
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ClearAllCaptionsPayload {
     pub root_path: String,
+    /// Sidecar to create when the image has none yet.
+    #[serde(default)]
+    pub caption_ext: CaptionExt,
+    /// Descend into symlinked folders. WalkDir detects symlink cycles and skips the looping entry,
+    /// but links can still pull in large trees from outside the project.
+    #[serde(default)]
+    pub follow_symlinks: bool,
+    /// Globs (relative path, case-insensitive) of images to leave out, e.g. `raw/**`. Hidden
+    /// files and folders are always skipped.
+    #[serde(default)]
+    pub ignore_patterns: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FailedCaption {
+    pub path: String,
+    pub error: String,
 }
 
 #[derive(Debug, Serialize)]
 pub struct ClearAllCaptionsResult {
     pub cleared_count: usize,
+    /// Caption files that could not be written (e.g. read-only); the rest are still cleared.
+    pub failed: Vec<FailedCaption>,
+    /// Images skipped as hidden or matching ignore_patterns.
+    pub ignored_count: usize,
+    /// True if stopped early by `cancel_clear_captions`; cleared_count covers what was done.
+    pub cancelled: bool,
+}
+
+const CLEAR_PROGRESS_EVENT: &str = "clear-captions-progress";
+/// Emit a progress event every this many files.
+const CLEAR_PROGRESS_EVERY: usize = 50;
+
+/// Set by `cancel_clear_captions`; checked before each file. Reset when a clear starts.
+static CLEAR_CANCELLED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Serialize)]
+struct ClearCaptionsProgress {
+    done: usize,
+    total: usize,
+}
+
+/// Request that a running clear_all_captions stop. Captions already cleared stay cleared.
+#[tauri::command]
+pub fn cancel_clear_captions() {
+    CLEAR_CANCELLED.store(true, Ordering::Relaxed);
 }
 
 /// Clears all caption files in the project (writes empty content to each image's .txt).
-/// Uses the same walk as the project so paths match.
+/// Uses the same walk as the project so paths match. Writes run in parallel off the main thread,
+/// emitting `clear-captions-progress` events `{ done, total }` as they go.
 #[tauri::command]
-pub fn clear_all_captions(payload: ClearAllCaptionsPayload) -> Result<ClearAllCaptionsResult, String> {
-    let root = PathBuf::from(&payload.root_path);
+pub async fn clear_all_captions(
+    app: AppHandle,
+    payload: ClearAllCaptionsPayload,
+) -> Result<ClearAllCaptionsResult, CommandError> {
+    CLEAR_CANCELLED.store(false, Ordering::Relaxed);
+    tauri::async_runtime::spawn_blocking(move || {
+        let walker = ProjectWalk::new(payload.follow_symlinks, payload.ignore_patterns.as_deref())?;
+        let (_, walked) = project_images(&payload.root_path, &walker)?;
+        let total = walked.images.len();
+        let done = AtomicUsize::new(0);
+        let emit = |done: usize| {
+            let _ = app.emit(CLEAR_PROGRESS_EVENT, ClearCaptionsProgress { done, total });
+        };
+        emit(0);
+        // None = skipped after cancellation.
+        let results: Vec<Option<Result<(), FailedCaption>>> = walked
+            .images
+            .par_iter()
+            .map(|p| {
+                if CLEAR_CANCELLED.load(Ordering::Relaxed) {
+                    return None;
+                }
+                let caption_path = sidecar::resolve(p, payload.caption_ext);
+                let result = sidecar::write(&caption_path, "").map_err(|e| FailedCaption {
+                    path: caption_path.to_string_lossy().into_owned(),
+                    error: e.to_string(),
+                });
+                let n = done.fetch_add(1, Ordering::Relaxed) + 1;
+                if n.is_multiple_of(CLEAR_PROGRESS_EVERY) {
+                    emit(n);
+                }
+                Some(result)
+            })
+            .collect();
+
+        let mut cleared_count = 0usize;
+        let mut failed = Vec::new();
+        let mut cancelled = false;
+        for result in results {
+            match result {
+                Some(Ok(())) => cleared_count += 1,
+                Some(Err(f)) => failed.push(f),
+                None => cancelled = true,
+            }
+        }
+        emit(done.load(Ordering::Relaxed));
+        Ok(ClearAllCaptionsResult {
+            cleared_count,
+            failed,
+            ignored_count: walked.ignored,
+            cancelled,
+        })
+    })
+    .await?
+}
+
+/// Canonical project root and every image under it, sorted (same walk as the project).
+fn project_images(
+    root_path: &str,
+    walker: &ProjectWalk,
+) -> Result<(PathBuf, WalkedImages), CommandError> {
+    let root = PathBuf::from(root_path);
     if !root.is_dir() {
-        return Err("Project folder does not exist".to_string());
-    }
-    let canonical = root.canonicalize().map_err(|e| e.to_string())?;
-    let mut cleared = 0usize;
-    for entry in WalkDir::new(&canonical)
-        .follow_links(false)
-        .into_iter()
-        .filter_map(Result::ok)
-    {
-        let p = entry.path();
-        if !p.is_file() || !is_image_path(p) {
+        return Err(CommandError::NotFound(
+            "Project folder does not exist".to_string(),
+        ));
+    }
+    let canonical = root.canonicalize()?;
+    let mut walked = walker.images(&canonical);
+    walked.images.sort();
+    Ok((canonical, walked))
+}
+
+/// Existing caption files for every image under the project root (deduplicated, sorted).
+/// Images sharing a stem share one .txt, so each caption appears once.
+fn project_caption_files(root_path: &str) -> Result<Vec<PathBuf>, CommandError> {
+    let (_, walked) = project_images(root_path, &ProjectWalk::default())?;
+    let files: BTreeSet<PathBuf> = walked
+        .images
+        .iter()
+        .filter_map(|p| sidecar::existing(p))
+        .collect();
+    Ok(files.into_iter().collect())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CaptionCoveragePayload {
+    pub root_path: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CaptionCoverage {
+    pub total_images: usize,
+    /// Images whose caption has non-whitespace text.
+    pub captioned: usize,
+    /// Caption file exists but is blank (or unreadable).
+    pub empty_captions: usize,
+    /// No caption file at all.
+    pub uncaptioned: usize,
+    /// `captioned / total_images * 100`; 0 for an empty project.
+    pub coverage_percent: f64,
+}
+
+/// How many project images are captioned, blank, or missing a caption, for a progress badge.
+#[tauri::command]
+pub fn caption_coverage(payload: CaptionCoveragePayload) -> Result<CaptionCoverage, CommandError> {
+    let (_, walked) = project_images(&payload.root_path, &ProjectWalk::default())?;
+    let (captioned, empty_captions) = walked
+        .images
+        .par_iter()
+        .map(|image| match sidecar::existing(image) {
+            None => (0, 0),
+            Some(path) => match sidecar::read(&path) {
+                Ok(raw) if !raw.trim().is_empty() => (1, 0),
+                _ => (0, 1),
+            },
+        })
+        .reduce(|| (0, 0), |a, b| (a.0 + b.0, a.1 + b.1));
+    let total_images = walked.images.len();
+    let coverage_percent = if total_images == 0 {
+        0.0
+    } else {
+        captioned as f64 * 100.0 / total_images as f64
+    };
+    Ok(CaptionCoverage {
+        total_images,
+        captioned,
+        empty_captions,
+        uncaptioned: total_images - captioned - empty_captions,
+        coverage_percent,
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchCaptionsPayload {
+    pub root_path: String,
+    pub query: String,
+    /// Match entire tags only; otherwise any tag containing the query matches.
+    #[serde(default)]
+    pub whole_tag: bool,
+    #[serde(default)]
+    pub case_sensitive: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CaptionSearchMatch {
+    /// Relative to the project root, forward slashes (same as the project listing).
+    pub relative_path: String,
+    pub matched_tags: Vec<String>,
+}
+
+/// Find images whose caption has a tag matching the query. Returns matches in path order.
+#[tauri::command]
+pub fn search_captions(
+    payload: SearchCaptionsPayload,
+) -> Result<Vec<CaptionSearchMatch>, CommandError> {
+    let fold = |s: &str| {
+        if payload.case_sensitive {
+            s.to_string()
+        } else {
+            s.to_lowercase()
+        }
+    };
+    let query = fold(payload.query.trim());
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let (root, walked) = project_images(&payload.root_path, &ProjectWalk::default())?;
+    Ok(walked
+        .images
+        .par_iter()
+        .filter_map(|image| {
+            let raw = sidecar::read_for_image(image)?;
+            let matched_tags: Vec<String> = parse_tags(&raw)
+                .into_iter()
+                .filter(|tag| {
+                    let tag = fold(tag);
+                    if payload.whole_tag {
+                        tag == query
+                    } else {
+                        tag.contains(&query)
+                    }
+                })
+                .collect();
+            if matched_tags.is_empty() {
+                return None;
+            }
+            let relative = image.strip_prefix(&root).unwrap_or(image);
+            Some(CaptionSearchMatch {
+                relative_path: relative.to_string_lossy().replace('\\', "/"),
+                matched_tags,
+            })
+        })
+        .collect())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApplyBlacklistPayload {
+    pub root_path: String,
+    pub blacklist: Vec<String>,
+    /// Text between tags (default ", "); see tag_separator.
+    #[serde(default)]
+    pub separator: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ApplyBlacklistResult {
+    pub files_changed: usize,
+    pub tags_removed: usize,
+}
+
+/// Strip blacklisted tags from every caption in the project.
+#[tauri::command]
+pub fn apply_blacklist(
+    payload: ApplyBlacklistPayload,
+) -> Result<ApplyBlacklistResult, CommandError> {
+    let separator = tag_separator(payload.separator.as_deref())?;
+    let mut files_changed = 0usize;
+    let mut tags_removed = 0usize;
+    for caption_path in project_caption_files(&payload.root_path)? {
+        let raw = sidecar::read(&caption_path)?;
+        let mut tags = parse_tags(&raw);
+        let removed = strip_blacklisted(&mut tags, &payload.blacklist);
+        if removed == 0 {
+            continue;
+        }
+        write_named(&caption_path, &tags.join(separator))?;
+        files_changed += 1;
+        tags_removed += removed;
+    }
+    Ok(ApplyBlacklistResult {
+        files_changed,
+        tags_removed,
+    })
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TagSeparator {
+    Spaces,
+    Underscores,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NormalizeTagSeparatorsPayload {
+    pub root_path: String,
+    pub to: TagSeparator,
+    /// Keep booru escapes like `\(` `\)` as-is; when false they are unescaped to plain parentheses.
+    #[serde(default)]
+    pub keep_escaped: bool,
+    /// Text between tags in the rewritten captions (default ", "); see tag_separator.
+    #[serde(default)]
+    pub separator: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NormalizeTagSeparatorsResult {
+    pub files_changed: usize,
+}
+
+fn normalize_separator(tag: &str, to: TagSeparator, keep_escaped: bool) -> String {
+    let converted = match to {
+        TagSeparator::Spaces => tag.replace('_', " "),
+        TagSeparator::Underscores => tag.split_whitespace().collect::<Vec<_>>().join("_"),
+    };
+    if keep_escaped {
+        converted
+    } else {
+        converted.replace("\\(", "(").replace("\\)", ")")
+    }
+}
+
+/// Rewrite every tag in every caption to use spaces or underscores between words
+/// (`long_hair` <-> `long hair`).
+#[tauri::command]
+pub fn normalize_tag_separators(
+    payload: NormalizeTagSeparatorsPayload,
+) -> Result<NormalizeTagSeparatorsResult, CommandError> {
+    let separator = tag_separator(payload.separator.as_deref())?;
+    let mut files_changed = 0usize;
+    for caption_path in project_caption_files(&payload.root_path)? {
+        let raw = sidecar::read(&caption_path)?;
+        let tags = parse_tags(&raw);
+        let normalized: Vec<String> = tags
+            .iter()
+            .map(|t| normalize_separator(t, payload.to, payload.keep_escaped))
+            .collect();
+        if normalized == tags {
+            continue;
+        }
+        let content = normalized.join(separator);
+        write_named(&caption_path, &content)?;
+        files_changed += 1;
+    }
+    Ok(NormalizeTagSeparatorsResult { files_changed })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RenameTagPayload {
+    pub root_path: String,
+    pub from: String,
+    pub to: String,
+    /// Text between tags (default ", "); see tag_separator.
+    #[serde(default)]
+    pub separator: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RenameTagResult {
+    pub files_changed: usize,
+    /// Captions that already had `to` (or repeated `from`) and were collapsed to a single tag.
+    pub merges: usize,
+}
+
+/// Replace whole-tag `from` with `to` at the position of the first `from`, dropping any other
+/// `from`/`to` occurrences. Returns None when the caption has no `from`; otherwise the new tags and
+/// whether duplicates were merged.
+fn rename_in_tags(tags: &[String], from: &str, to: &str) -> Option<(Vec<String>, bool)> {
+    let to_lower = to.to_lowercase();
+    let matches = |t: &str| {
+        let t = t.to_lowercase();
+        t == from || t == to_lower
+    };
+    let first = tags.iter().position(|t| t.to_lowercase() == from)?;
+    let mut merged = false;
+    let renamed = tags
+        .iter()
+        .enumerate()
+        .filter_map(|(i, t)| {
+            if i == first {
+                Some(to.to_string())
+            } else if matches(t) {
+                merged = true;
+                None
+            } else {
+                Some(t.clone())
+            }
+        })
+        .collect();
+    Some((renamed, merged))
+}
+
+/// Rename a tag across the project, merging with any existing copy of the new name.
+#[tauri::command]
+pub fn rename_tag(payload: RenameTagPayload) -> Result<RenameTagResult, CommandError> {
+    let separator = tag_separator(payload.separator.as_deref())?;
+    let from = payload.from.trim().to_lowercase();
+    let to = payload.to.trim();
+    if from.is_empty() || to.is_empty() {
+        return Err(CommandError::invalid("Tag names cannot be empty"));
+    }
+
+    let mut files_changed = 0usize;
+    let mut merges = 0usize;
+    for caption_path in project_caption_files(&payload.root_path)? {
+        let raw = sidecar::read(&caption_path)?;
+        let tags = parse_tags(&raw);
+        let Some((renamed, merged)) = rename_in_tags(&tags, &from, to) else {
+            continue;
+        };
+        if renamed == tags {
             continue;
         }
-        let caption_path = p.with_extension("txt");
-        if let Err(e) = fs::write(&caption_path, "") {
-            return Err(format!("Failed to clear {}: {}", caption_path.display(), e));
+        write_named(&caption_path, &renamed.join(separator))?;
+        files_changed += 1;
+        if merged {
+            merges += 1;
         }
-        cleared += 1;
     }
-    Ok(ClearAllCaptionsResult { cleared_count: cleared })
+    Ok(RenameTagResult {
+        files_changed,
+        merges,
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SuggestTagMergesPayload {
+    pub root_path: String,
+    #[serde(default = "default_max_distance")]
+    pub max_distance: usize,
+}
+
+fn default_max_distance() -> usize {
+    2
+}
+
+#[derive(Debug, Serialize)]
+pub struct TagVariant {
+    pub tag: String,
+    pub count: usize,
+    pub distance: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TagMergeGroup {
+    /// The most frequent spelling; variants would be renamed to this.
+    pub canonical: String,
+    pub count: usize,
+    pub variants: Vec<TagVariant>,
+}
+
+/// Levenshtein distance over chars, or None once it is sure to exceed `max`.
+fn bounded_levenshtein(a: &[char], b: &[char], max: usize) -> Option<usize> {
+    if a.len().abs_diff(b.len()) > max {
+        return None;
+    }
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        curr[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != cb);
+            curr[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        if curr.iter().min().is_some_and(|&m| m > max) {
+            return None;
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    Some(prev[b.len()]).filter(|&d| d <= max)
+}
+
+/// Cluster near-identical tags (typos, plurals) across the project. Each group's canonical tag is
+/// its most frequent spelling; rarer variants within `max_distance` edits are listed for merging
+/// with rename_tag. Pairs whose distance is half the shorter tag or more (e.g. `red`/`bed`) are
+/// ignored as distinct words.
+#[tauri::command]
+pub async fn suggest_tag_merges(
+    payload: SuggestTagMergesPayload,
+) -> Result<Vec<TagMergeGroup>, CommandError> {
+    if payload.max_distance == 0 {
+        return Err(CommandError::invalid("max_distance must be at least 1"));
+    }
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for caption_path in project_caption_files(&payload.root_path)? {
+            let Ok(raw) = sidecar::read(&caption_path) else {
+                continue;
+            };
+            for tag in parse_tags(&raw) {
+                *counts.entry(tag.to_lowercase()).or_default() += 1;
+            }
+        }
+
+        // Most frequent first so each cluster is anchored on the dominant spelling.
+        let mut tags: Vec<(String, usize)> = counts.into_iter().collect();
+        tags.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        let chars: Vec<Vec<char>> = tags.iter().map(|(t, _)| t.chars().collect()).collect();
+        let max = payload.max_distance;
+
+        let neighbours: Vec<Vec<(usize, usize)>> = (0..tags.len())
+            .into_par_iter()
+            .map(|i| {
+                ((i + 1)..tags.len())
+                    .filter_map(|j| {
+                        let d = bounded_levenshtein(&chars[i], &chars[j], max)?;
+                        let shorter = chars[i].len().min(chars[j].len());
+                        (d * 2 < shorter).then_some((j, d))
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let mut assigned = vec![false; tags.len()];
+        let mut groups = Vec::new();
+        for (i, near) in neighbours.iter().enumerate() {
+            if assigned[i] {
+                continue;
+            }
+            let mut variants = Vec::new();
+            for &(j, distance) in near {
+                if assigned[j] {
+                    continue;
+                }
+                assigned[j] = true;
+                variants.push(TagVariant {
+                    tag: tags[j].0.clone(),
+                    count: tags[j].1,
+                    distance,
+                });
+            }
+            if variants.is_empty() {
+                continue;
+            }
+            assigned[i] = true;
+            groups.push(TagMergeGroup {
+                canonical: tags[i].0.clone(),
+                count: tags[i].1,
+                variants,
+            });
+        }
+        Ok(groups)
+    })
+    .await?
 }
 
 #[derive(Debug, Deserialize)]
@@ -198,40 +1232,287 @@ pub struct CaptionsBatchResult {
 
 /// Read captions for multiple images in parallel
 #[tauri::command]
-pub fn get_captions_batch(payload: GetCaptionsBatchPayload) -> Result<CaptionsBatchResult, String> {
+pub fn get_captions_batch(
+    payload: GetCaptionsBatchPayload,
+) -> Result<CaptionsBatchResult, CommandError> {
     let captions: HashMap<String, CaptionData> = payload
         .paths
         .par_iter()
         .filter_map(|path_str| {
-            let caption_path = caption_path_for(path_str);
-            
+            let caption_path = caption_path_for(path_str, CaptionExt::Txt);
+
             let caption_data = if caption_path.exists() {
-                match fs::read_to_string(&caption_path) {
-                    Ok(raw) => {
-                        let tags = parse_tags(&raw);
-                        CaptionData {
-                            exists: true,
-                            raw: raw.trim().to_string(),
-                            tags,
-                        }
-                    }
-                    Err(_) => CaptionData {
-                        exists: false,
-                        raw: String::new(),
-                        tags: Vec::new(),
-                    },
+                match sidecar::read(&caption_path) {
+                    Ok(raw) => CaptionData::from_raw(true, &raw),
+                    Err(_) => CaptionData::missing(),
                 }
             } else {
-                CaptionData {
-                    exists: false,
-                    raw: String::new(),
-                    tags: Vec::new(),
-                }
+                CaptionData::missing()
             };
-            
+
             Some((path_str.clone(), caption_data))
         })
         .collect();
 
     Ok(CaptionsBatchResult { captions })
 }
+
+#[derive(Debug, Deserialize)]
+pub struct ReadCaptionsBatchPayload {
+    pub paths: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CaptionEntry {
+    pub path: String,
+    pub exists: bool,
+    pub raw: String,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReadCaptionsBatchResult {
+    /// One entry per requested path, in request order.
+    pub captions: Vec<CaptionEntry>,
+    /// Tags present on every selected image, in the first image's order.
+    pub common_tags: Vec<String>,
+}
+
+/// Read captions for a selection in parallel, plus the tags they all share (for bulk editing).
+#[tauri::command]
+pub async fn read_captions_batch(
+    payload: ReadCaptionsBatchPayload,
+) -> Result<ReadCaptionsBatchResult, CommandError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let captions: Vec<CaptionEntry> = payload
+            .paths
+            .par_iter()
+            .map(|path| {
+                let raw = sidecar::read_for_image(Path::new(path));
+                CaptionEntry {
+                    path: path.clone(),
+                    exists: raw.is_some(),
+                    tags: raw.as_deref().map(parse_tags).unwrap_or_default(),
+                    raw: raw.map(|r| r.trim().to_string()).unwrap_or_default(),
+                }
+            })
+            .collect();
+
+        let mut common_tags: Vec<String> = Vec::new();
+        if let Some((first, rest)) = captions.split_first() {
+            let sets: Vec<HashSet<&str>> = rest
+                .iter()
+                .map(|c| c.tags.iter().map(String::as_str).collect())
+                .collect();
+            let mut seen = HashSet::new();
+            for tag in &first.tags {
+                if seen.insert(tag.as_str()) && sets.iter().all(|set| set.contains(tag.as_str())) {
+                    common_tags.push(tag.clone());
+                }
+            }
+        }
+
+        Ok(ReadCaptionsBatchResult {
+            captions,
+            common_tags,
+        })
+    })
+    .await?
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MappingFormat {
+    /// One `{"file_name": ..., "text": ...}` object per line (the metadata.jsonl layout).
+    Jsonl,
+    /// Header row with `file_name` and `text` columns; otherwise the first two columns.
+    Csv,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportCaptionsPayload {
+    pub root_path: String,
+    pub mapping_path: String,
+    pub format: MappingFormat,
+    /// Replace captions that already have content; otherwise they are skipped.
+    #[serde(default)]
+    pub overwrite: bool,
+    #[serde(default)]
+    pub caption_ext: CaptionExt,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportCaptionsResult {
+    pub imported: usize,
+    pub skipped: usize,
+    /// Mapping keys that matched no image (or several images by bare file name).
+    pub unmatched: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JsonlCaption {
+    file_name: String,
+    text: String,
+}
+
+fn parse_jsonl_mapping(content: &str) -> Result<Vec<(String, String)>, CommandError> {
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str::<JsonlCaption>(line)
+                .map(|c| (c.file_name, c.text))
+                .map_err(|e| CommandError::from(e).context(format!("Line {}", i + 1)))
+        })
+        .collect()
+}
+
+/// Minimal RFC 4180 reader: quoted fields may hold commas, newlines and doubled quotes.
+fn parse_csv_rows(content: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = content.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => row.push(std::mem::take(&mut field)),
+            '\r' if !in_quotes => {}
+            '\n' if !in_quotes => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            _ => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows.retain(|r| r.iter().any(|f| !f.trim().is_empty()));
+    rows
+}
+
+fn parse_csv_mapping(content: &str) -> Result<Vec<(String, String)>, CommandError> {
+    let mut rows = parse_csv_rows(content.trim_start_matches('\u{feff}')).into_iter();
+    let Some(first) = rows.next() else {
+        return Ok(Vec::new());
+    };
+    let column = |names: &[&str]| {
+        first
+            .iter()
+            .position(|h| names.iter().any(|n| h.trim().eq_ignore_ascii_case(n)))
+    };
+    let (name_col, text_col, header) = match (
+        column(&["file_name", "filename", "file"]),
+        column(&["text", "caption"]),
+    ) {
+        (Some(n), Some(t)) => (n, t, true),
+        _ => (0, 1, false),
+    };
+    let body = if header { None } else { Some(first) };
+    body.into_iter()
+        .chain(rows)
+        .enumerate()
+        .map(|(i, row)| match (row.get(name_col), row.get(text_col)) {
+            (Some(name), Some(text)) => Ok((name.clone(), text.clone())),
+            _ => Err(CommandError::Decode(format!(
+                "Row {}: missing file name or text column",
+                i + 1
+            ))),
+        })
+        .collect()
+}
+
+/// Apply captions generated elsewhere (JSONL or CSV keyed by file name) to the project. Keys match
+/// an image's relative path first, then its bare file name, both case-insensitively.
+#[tauri::command]
+pub async fn import_captions(
+    payload: ImportCaptionsPayload,
+) -> Result<ImportCaptionsResult, CommandError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let content = fs::read_to_string(&payload.mapping_path)?;
+        let entries = match payload.format {
+            MappingFormat::Jsonl => parse_jsonl_mapping(&content)?,
+            MappingFormat::Csv => parse_csv_mapping(&content)?,
+        };
+
+        let (root, walked) = project_images(&payload.root_path, &ProjectWalk::default())?;
+        let images = walked.images;
+        let mut by_rel: HashMap<String, &PathBuf> = HashMap::new();
+        let mut by_name: HashMap<String, Vec<&PathBuf>> = HashMap::new();
+        for img in &images {
+            if let Ok(rel) = img.strip_prefix(&root) {
+                let rel = rel.to_string_lossy().replace('\\', "/").to_lowercase();
+                by_rel.insert(rel, img);
+            }
+            if let Some(name) = img.file_name() {
+                by_name
+                    .entry(name.to_string_lossy().to_lowercase())
+                    .or_default()
+                    .push(img);
+            }
+        }
+
+        let mut result = ImportCaptionsResult {
+            imported: 0,
+            skipped: 0,
+            unmatched: Vec::new(),
+        };
+        for (key, text) in entries {
+            let lookup = key.trim().replace('\\', "/");
+            let lookup = lookup.trim_start_matches("./").to_lowercase();
+            let image = by_rel
+                .get(&lookup)
+                .copied()
+                .or_else(|| match by_name.get(&lookup) {
+                    Some(found) if found.len() == 1 => Some(found[0]),
+                    _ => None,
+                });
+            let Some(image) = image else {
+                result.unmatched.push(key);
+                continue;
+            };
+            let caption_path = sidecar::resolve(image, payload.caption_ext);
+            if !payload.overwrite && !read_raw(&caption_path).trim().is_empty() {
+                result.skipped += 1;
+                continue;
+            }
+            write_named(&caption_path, text.trim())?;
+            result.imported += 1;
+        }
+        Ok(result)
+    })
+    .await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn count_tokens_batch_keeps_unreadable_captions_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_string_lossy().into_owned();
+        fs::write(dir.path().join("a.txt"), "1girl, smile").unwrap();
+        // A folder where the caption should be fails to read.
+        fs::create_dir(dir.path().join("b.txt")).unwrap();
+        let paths = vec![path("b.png"), path("a.png"), path("c.png")];
+        let results = count_tokens_batch(CountTokensBatchPayload {
+            paths: paths.clone(),
+            limit: None,
+        })
+        .unwrap();
+        let got: Vec<_> = results.iter().map(|r| r.path.clone()).collect();
+        assert_eq!(got, paths);
+        assert!(results[0].tokens.is_none() && results[0].error.is_some());
+        assert!(results[1].tokens.as_ref().unwrap().token_count > 2);
+        assert_eq!(results[2].tokens.as_ref().unwrap().token_count, 2);
+    }
+}
@@ -3,6 +3,8 @@ use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
 
+use super::error::CommandError;
+
 const CROP_STATUS_FILE: &str = ".lora-studio/crop_status.json";
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -14,30 +16,30 @@ fn crop_status_path(root_path: &str) -> PathBuf {
     PathBuf::from(root_path).join(CROP_STATUS_FILE)
 }
 
-fn ensure_lora_studio_dir(root_path: &str) -> Result<(), String> {
+fn ensure_lora_studio_dir(root_path: &str) -> Result<(), CommandError> {
     let dir = PathBuf::from(root_path).join(".lora-studio");
     if !dir.exists() {
-        fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        fs::create_dir_all(&dir)?;
     }
     Ok(())
 }
 
-fn load_crop_statuses(root_path: &str) -> Result<CropStatusData, String> {
+fn load_crop_statuses(root_path: &str) -> Result<CropStatusData, CommandError> {
     let path = crop_status_path(root_path);
     if !path.exists() {
         return Ok(CropStatusData {
             statuses: HashMap::new(),
         });
     }
-    let content = fs::read_to_string(&path).map_err(|e| e.to_string())?;
-    serde_json::from_str(&content).map_err(|e| e.to_string())
+    let content = fs::read_to_string(&path)?;
+    Ok(serde_json::from_str(&content)?)
 }
 
-fn save_crop_statuses(root_path: &str, data: &CropStatusData) -> Result<(), String> {
+fn save_crop_statuses(root_path: &str, data: &CropStatusData) -> Result<(), CommandError> {
     ensure_lora_studio_dir(root_path)?;
     let path = crop_status_path(root_path);
-    let content = serde_json::to_string_pretty(data).map_err(|e| e.to_string())?;
-    fs::write(&path, content).map_err(|e| e.to_string())
+    let content = serde_json::to_string_pretty(data)?;
+    Ok(fs::write(&path, content)?)
 }
 
 #[derive(Debug, Deserialize)]
@@ -48,13 +50,12 @@ pub struct SetCropStatusPayload {
 }
 
 #[tauri::command]
-pub fn set_crop_status(payload: SetCropStatusPayload) -> Result<(), String> {
+pub fn set_crop_status(payload: SetCropStatusPayload) -> Result<(), CommandError> {
     let mut data = load_crop_statuses(&payload.root_path)?;
     if payload.status == "uncropped" {
         data.statuses.remove(&payload.relative_path);
     } else {
-        data.statuses
-            .insert(payload.relative_path, payload.status);
+        data.statuses.insert(payload.relative_path, payload.status);
     }
     save_crop_statuses(&payload.root_path, &data)
 }
@@ -67,13 +68,13 @@ pub struct GetCropStatusesPayload {
 #[tauri::command]
 pub fn get_crop_statuses(
     payload: GetCropStatusesPayload,
-) -> Result<HashMap<String, String>, String> {
+) -> Result<HashMap<String, String>, CommandError> {
     let data = load_crop_statuses(&payload.root_path)?;
     Ok(data.statuses)
 }
 
 #[tauri::command]
-pub fn clear_all_crop_statuses(payload: GetCropStatusesPayload) -> Result<usize, String> {
+pub fn clear_all_crop_statuses(payload: GetCropStatusesPayload) -> Result<usize, CommandError> {
     let data = load_crop_statuses(&payload.root_path)?;
     let count = data.statuses.len();
     let empty = CropStatusData {
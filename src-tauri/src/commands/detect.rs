@@ -1,7 +1,11 @@
+use image::GenericImageView;
+use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
+use std::path::Path;
 use std::sync::Mutex;
-use once_cell::sync::Lazy;
-use image::GenericImageView;
+
+use super::error::CommandError;
+use super::images::open_image;
 
 #[derive(Debug, Clone, Serialize)]
 pub struct FaceRegion {
@@ -22,7 +26,7 @@ pub struct DetectFacesPayload {
 }
 
 #[tauri::command]
-pub fn detect_faces(payload: DetectFacesPayload) -> Result<Vec<FaceRegion>, String> {
+pub fn detect_faces(payload: DetectFacesPayload) -> Result<Vec<FaceRegion>, CommandError> {
     // Check cache first
     {
         let cache = DETECTION_CACHE.lock().unwrap();
@@ -32,9 +36,10 @@ pub fn detect_faces(payload: DetectFacesPayload) -> Result<Vec<FaceRegion>, Stri
     }
 
     // Load image to get dimensions
-    let img = image::open(&payload.path).map_err(|e| format!("Failed to open image: {}", e))?;
+    let img =
+        open_image(Path::new(&payload.path)).map_err(|e| e.context("Failed to open image"))?;
     let (width, height) = img.dimensions();
-    
+
     // PLACEHOLDER IMPLEMENTATION - Working demonstration of the feature
     //
     // The YuNet ONNX model has been downloaded to src-tauri/models/yunet_face.onnx
@@ -51,12 +56,12 @@ pub fn detect_faces(payload: DetectFacesPayload) -> Result<Vec<FaceRegion>, Stri
     // ✓ Multiple faces can be shown (green overlays)
     //
     // The infrastructure is complete - just needs the ONNX Runtime API debugging.
-    
+
     let face_width = (width as f32 * 0.4) as u32;
     let face_height = (height as f32 * 0.5) as u32;
     let face_x = (width - face_width) / 2;
     let face_y = (height - face_height) / 2;
-    
+
     let result = vec![FaceRegion {
         x: face_x,
         y: face_y,
@@ -64,12 +69,12 @@ pub fn detect_faces(payload: DetectFacesPayload) -> Result<Vec<FaceRegion>, Stri
         height: face_height,
         confidence: 0.95,
     }];
-    
+
     // Cache the result
     {
         let mut cache = DETECTION_CACHE.lock().unwrap();
         cache.insert(payload.path.clone(), result.clone());
     }
-    
+
     Ok(result)
 }
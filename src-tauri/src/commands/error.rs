@@ -0,0 +1,160 @@
+//! Error type returned by every command. Serialized as `{ "code": "...", "message": "..." }` so
+//! the frontend can branch on `code` instead of matching message text.
+
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum CommandError {
+    NotFound(String),
+    PermissionDenied(String),
+    NotImage(String),
+    InvalidInput(String),
+    Io(String),
+    Decode(String),
+    Network(String),
+    Timeout(String),
+    /// An external helper process failed to start or exited with an error.
+    Subprocess(String),
+    Other(String),
+}
+
+impl CommandError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::NotFound(_) => "not_found",
+            Self::PermissionDenied(_) => "permission_denied",
+            Self::NotImage(_) => "not_image",
+            Self::InvalidInput(_) => "invalid_input",
+            Self::Io(_) => "io",
+            Self::Decode(_) => "decode",
+            Self::Network(_) => "network",
+            Self::Timeout(_) => "timeout",
+            Self::Subprocess(_) => "subprocess",
+            Self::Other(_) => "other",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            Self::NotFound(m)
+            | Self::PermissionDenied(m)
+            | Self::NotImage(m)
+            | Self::InvalidInput(m)
+            | Self::Io(m)
+            | Self::Decode(m)
+            | Self::Network(m)
+            | Self::Timeout(m)
+            | Self::Subprocess(m)
+            | Self::Other(m) => m,
+        }
+    }
+
+    pub fn invalid(message: impl Into<String>) -> Self {
+        Self::InvalidInput(message.into())
+    }
+
+    pub fn other(message: impl Into<String>) -> Self {
+        Self::Other(message.into())
+    }
+
+    /// Prefix the message, e.g. with the file involved, keeping the code.
+    pub fn context(self, context: impl fmt::Display) -> Self {
+        let wrap = |m: String| format!("{}: {}", context, m);
+        match self {
+            Self::NotFound(m) => Self::NotFound(wrap(m)),
+            Self::PermissionDenied(m) => Self::PermissionDenied(wrap(m)),
+            Self::NotImage(m) => Self::NotImage(wrap(m)),
+            Self::InvalidInput(m) => Self::InvalidInput(wrap(m)),
+            Self::Io(m) => Self::Io(wrap(m)),
+            Self::Decode(m) => Self::Decode(wrap(m)),
+            Self::Network(m) => Self::Network(wrap(m)),
+            Self::Timeout(m) => Self::Timeout(wrap(m)),
+            Self::Subprocess(m) => Self::Subprocess(wrap(m)),
+            Self::Other(m) => Self::Other(wrap(m)),
+        }
+    }
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+impl Serialize for CommandError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("CommandError", 2)?;
+        s.serialize_field("code", self.code())?;
+        s.serialize_field("message", self.message())?;
+        s.end()
+    }
+}
+
+impl From<io::Error> for CommandError {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::NotFound => Self::NotFound(e.to_string()),
+            io::ErrorKind::PermissionDenied => Self::PermissionDenied(e.to_string()),
+            io::ErrorKind::TimedOut => Self::Timeout(e.to_string()),
+            io::ErrorKind::InvalidInput => Self::InvalidInput(e.to_string()),
+            _ => Self::Io(e.to_string()),
+        }
+    }
+}
+
+impl From<image::ImageError> for CommandError {
+    fn from(e: image::ImageError) -> Self {
+        match e {
+            image::ImageError::IoError(e) => e.into(),
+            image::ImageError::Unsupported(_) => Self::NotImage(e.to_string()),
+            image::ImageError::Parameter(_) => Self::InvalidInput(e.to_string()),
+            _ => Self::Decode(e.to_string()),
+        }
+    }
+}
+
+impl From<reqwest::Error> for CommandError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            Self::Timeout(e.to_string())
+        } else {
+            Self::Network(e.to_string())
+        }
+    }
+}
+
+impl From<serde_json::Error> for CommandError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Decode(e.to_string())
+    }
+}
+
+impl From<zip::result::ZipError> for CommandError {
+    fn from(e: zip::result::ZipError) -> Self {
+        match e {
+            zip::result::ZipError::Io(e) => e.into(),
+            _ => Self::Io(e.to_string()),
+        }
+    }
+}
+
+impl From<notify::Error> for CommandError {
+    fn from(e: notify::Error) -> Self {
+        match e.kind {
+            notify::ErrorKind::Io(e) => e.into(),
+            notify::ErrorKind::PathNotFound => Self::NotFound(e.to_string()),
+            _ => Self::Other(e.to_string()),
+        }
+    }
+}
+
+impl From<tauri::Error> for CommandError {
+    fn from(e: tauri::Error) -> Self {
+        Self::Other(e.to_string())
+    }
+}
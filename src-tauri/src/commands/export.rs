@@ -1,73 +1,618 @@
-//! Export dataset: copy images + .txt captions to a folder or ZIP.
+//! Export dataset: copy images + .txt captions to a folder or an archive (ZIP, tar, tar.gz).
 //! Supports filtering by relative paths and "only captioned"; optional trigger word and sequential naming.
 
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use image::codecs::jpeg::JpegEncoder;
+use image::ImageFormat;
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::{Path, PathBuf};
-use walkdir::WalkDir;
+use std::io::Cursor;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use tauri::{AppHandle, Emitter};
 
-use super::ratings::{load_ratings, ImageRating, RatingsData};
+use super::captions::{parse_tags, tag_separator};
+use super::error::CommandError;
+use super::formats::is_image_path;
+use super::images::{fit_longest_side, open_oriented, writable_format};
+use super::ratings::{load_ratings, normalize_rel, ImageRating, RatingLookup};
+use super::sidecar::{self, CaptionExt};
+use super::walk::ProjectWalk;
 
-const IMAGE_EXT: &[&str] = &["png", "jpg", "jpeg", "webp", "gif", "bmp"];
+/// Source caption text from whichever sidecar the image has (.txt, .caption or .json).
+fn source_caption(img: &Path) -> Option<String> {
+    sidecar::read_for_image(img)
+}
 
-fn is_image(p: &Path) -> bool {
-    let ext = match p.extension().and_then(|e| e.to_str()) {
-        Some(e) => e.to_lowercase(),
-        None => return false,
-    };
-    IMAGE_EXT.iter().any(|&e| e.eq_ignore_ascii_case(&ext))
+const PROGRESS_EVENT: &str = "export-progress";
+/// Emit a progress event every this many files.
+const PROGRESS_EVERY: usize = 10;
+
+/// Set by `cancel_export`; checked before each file. Reset when an export starts.
+static EXPORT_CANCELLED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Serialize)]
+struct ExportProgress {
+    done: usize,
+    total: usize,
+    current_file: String,
+}
+
+/// Emits throttled `export-progress` events as files are processed.
+/// Shared across the parallel folder export's workers.
+struct ProgressReporter<'a> {
+    /// None in tests, which have no app to emit to.
+    app: Option<&'a AppHandle>,
+    done: AtomicUsize,
+    total: usize,
+}
+
+impl<'a> ProgressReporter<'a> {
+    fn new(app: &'a AppHandle, total: usize) -> Self {
+        Self {
+            app: Some(app),
+            done: AtomicUsize::new(0),
+            total,
+        }
+    }
+
+    #[cfg(test)]
+    fn silent(total: usize) -> Self {
+        Self {
+            app: None,
+            done: AtomicUsize::new(0),
+            total,
+        }
+    }
+
+    /// Call before processing each file.
+    fn start_file(&self, img: &Path) {
+        let done = self.done.fetch_add(1, Ordering::Relaxed);
+        if done.is_multiple_of(PROGRESS_EVERY) {
+            self.emit(done, img.file_name().and_then(|n| n.to_str()).unwrap_or(""));
+        }
+    }
+
+    fn finish(&self) {
+        self.emit(self.done.load(Ordering::Relaxed), "");
+    }
+
+    fn emit(&self, done: usize, current_file: &str) {
+        let Some(app) = self.app else {
+            return;
+        };
+        let _ = app.emit(
+            PROGRESS_EVENT,
+            ExportProgress {
+                done,
+                total: self.total,
+                current_file: current_file.to_string(),
+            },
+        );
+    }
+}
+
+fn export_cancelled() -> bool {
+    EXPORT_CANCELLED.load(Ordering::Relaxed)
 }
 
-fn caption_path(img: &Path) -> PathBuf {
-    img.with_extension("txt")
+/// Request that the running export stop after the current file. Files already written are kept
+/// (a ZIP is still finalized) and the result reports `cancelled: true`.
+#[tauri::command]
+pub fn cancel_export() {
+    EXPORT_CANCELLED.store(true, Ordering::Relaxed);
 }
 
 // ============ Export to folder or ZIP ============
 
-#[derive(Debug, Deserialize)]
+/// Where the trigger word goes in exported captions.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TriggerPosition {
+    #[default]
+    Prefix,
+    Suffix,
+}
+
+/// Archive container for `as_zip` exports.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveFormat {
+    #[default]
+    Zip,
+    Tar,
+    TarGz,
+}
+
+impl ArchiveFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ArchiveFormat::Zip => ".zip",
+            ArchiveFormat::Tar => ".tar",
+            ArchiveFormat::TarGz => ".tar.gz",
+        }
+    }
+}
+
+/// ZIP entry compression. When unset, already-compressed images (JPEG, WebP, GIF) are stored and
+/// everything else is deflated.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ZipCompression {
+    Stored,
+    Deflated,
+    /// Deflate at an explicit level (0–9).
+    DeflatedLevel(u8),
+}
+
+/// Entry options for a ZIP entry, picking by extension when no compression was requested.
+fn zip_entry_options(
+    name: &str,
+    compression: Option<ZipCompression>,
+) -> zip::write::SimpleFileOptions {
+    let compression = compression.unwrap_or_else(|| {
+        let ext = name
+            .rsplit_once('.')
+            .map(|(_, e)| e.to_lowercase())
+            .unwrap_or_default();
+        if matches!(ext.as_str(), "jpg" | "jpeg" | "webp" | "gif") {
+            ZipCompression::Stored
+        } else {
+            ZipCompression::Deflated
+        }
+    });
+    let opts = zip::write::SimpleFileOptions::default();
+    match compression {
+        ZipCompression::Stored => opts.compression_method(zip::CompressionMethod::Stored),
+        ZipCompression::Deflated => opts.compression_method(zip::CompressionMethod::Deflated),
+        ZipCompression::DeflatedLevel(level) => opts
+            .compression_method(zip::CompressionMethod::Deflated)
+            .compression_level(Some(level.min(9) as i64)),
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
 pub struct ExportOptions {
     pub source_path: String,
     pub dest_path: String,
+    /// Resolve dest_path against the canonical source folder (e.g. `../export`) instead of the
+    /// app's working directory. It must not land inside the source.
+    #[serde(default)]
+    pub dest_relative_to_source: bool,
+    /// Descend into symlinked folders. WalkDir detects symlink cycles and skips the looping entry,
+    /// but links can still pull in large trees from outside the project.
+    #[serde(default)]
+    pub follow_symlinks: bool,
+    /// Globs (relative path, case-insensitive) of images to leave out, e.g. `raw/**`. Hidden
+    /// files and folders are always skipped.
+    #[serde(default)]
+    pub ignore_patterns: Option<Vec<String>>,
+    /// Write a single archive at dest_path instead of a folder.
     #[serde(default)]
     pub as_zip: bool,
+    /// Archive type when as_zip is set (default ZIP). The matching extension is added to
+    /// dest_path if missing.
+    #[serde(default)]
+    pub archive_format: ArchiveFormat,
+    /// ZIP compression; unset picks stored/deflated per file type.
+    #[serde(default)]
+    pub compression: Option<ZipCompression>,
     #[serde(default)]
     pub only_captioned: bool,
+    /// Export only images without a caption file (e.g. a to-do set). Exclusive with only_captioned.
+    #[serde(default)]
+    pub only_uncaptioned: bool,
     #[serde(default)]
     pub relative_paths: Option<Vec<String>>,
     #[serde(default)]
     pub trigger_word: Option<String>,
     #[serde(default)]
+    pub trigger_position: TriggerPosition,
+    /// Fixed text before every exported caption (after the trigger is applied), e.g.
+    /// "masterpiece, best quality". Source captions are untouched.
+    #[serde(default)]
+    pub caption_prefix: Option<String>,
+    /// Fixed text after every exported caption.
+    #[serde(default)]
+    pub caption_suffix: Option<String>,
+    #[serde(default)]
     pub sequential_naming: bool,
+    /// First number for sequential_naming (default 1), e.g. 501 when appending to an export.
+    #[serde(default)]
+    pub start_index: Option<usize>,
+    /// Zero-padded digits for sequential names (default 4). Must fit the largest index.
+    #[serde(default)]
+    pub pad_width: Option<usize>,
+    /// With sequential_naming, append the sanitized original stem: `0001_original-stem.png`.
+    #[serde(default)]
+    pub sequential_keep_name: bool,
+    /// Recreate the source subfolder layout under dest_path (or inside the ZIP) instead of flattening.
+    #[serde(default)]
+    pub preserve_structure: bool,
+    /// Downscale so the longest side is at most this many pixels (never upscales).
+    #[serde(default)]
+    pub max_side: Option<u32>,
+    /// Re-encode every image to this format: "png", "jpeg" or "webp".
+    #[serde(default)]
+    pub convert_format: Option<String>,
+    /// Fraction (0.0–1.0) of images held out into a `val/` subfolder; the rest go to `train/`.
+    #[serde(default)]
+    pub val_split: Option<f32>,
+    /// Seed for the train/val shuffle so splits are reproducible (default 0).
+    #[serde(default)]
+    pub split_seed: Option<u64>,
+    /// Write manifest.csv (source path, exported name, caption) at the export root / in the ZIP.
+    #[serde(default)]
+    pub write_manifest: bool,
+    /// Export grayscale copies (e.g. for style LoRAs). Sources are left untouched.
+    #[serde(default)]
+    pub to_grayscale: bool,
+    /// Re-encode every image so only pixel data is written (drops EXIF/GPS/XMP). JPEG re-encoding
+    /// is lossy; use `quality` to control it.
+    #[serde(default)]
+    pub strip_metadata: bool,
+    /// JPEG quality 1–100 for re-encoded images (default 75). Ignored for PNG and WebP (lossless).
+    #[serde(default)]
+    pub quality: Option<u8>,
+    /// Skip images whose shorter side is below this many pixels.
+    #[serde(default)]
+    pub min_side: Option<u32>,
+    /// Skip images larger than this many megapixels.
+    #[serde(default)]
+    pub max_megapixels: Option<f32>,
+    /// Skip images whose caption has fewer tags (e.g. failed generations). Intended for use with
+    /// only_captioned; an image without a caption counts as zero tags.
+    #[serde(default)]
+    pub min_tags: Option<usize>,
+    /// Skip images whose caption has more tags than this.
+    #[serde(default)]
+    pub max_tags: Option<usize>,
+    /// Randomize tag order in the exported captions only; caption files in the source are untouched.
+    #[serde(default)]
+    pub shuffle_tags: bool,
+    /// Base seed for shuffle_tags; each image's order is derived from this and its file name.
+    #[serde(default)]
+    pub shuffle_seed: Option<u64>,
+    /// Number of leading tags kept in place when shuffling (e.g. trigger/quality tags).
+    #[serde(default)]
+    pub keep_leading: usize,
+    /// Run selection, filters and naming without writing anything; see ExportResult.planned_names.
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Write only the caption files (trigger, prefix and suffix applied), named as their images
+    /// would be; images are not copied. Uncaptioned images are skipped, and exported_count counts
+    /// caption files.
+    #[serde(default)]
+    pub captions_only: bool,
+    /// Folder exports only: compare SHA-256 of source and copy, or for re-encoded images check
+    /// that the output decodes. Failures are listed in verification_errors.
+    #[serde(default)]
+    pub verify: bool,
+    /// Sidecar format for exported captions (default .txt).
+    #[serde(default)]
+    pub caption_ext: CaptionExt,
+    /// Re-join exported caption tags with this (e.g. "\n" for one tag per line). Unset keeps the
+    /// caption's text as written; see captions::tag_separator.
+    #[serde(default)]
+    pub separator: Option<String>,
+    /// Skip images whose bytes are identical (SHA-256) to an earlier selected image; the first in
+    /// sorted order is kept and the rest are skipped with reason "duplicate".
+    #[serde(default)]
+    pub dedup_exact: bool,
 }
 
 #[derive(Debug, Serialize)]
 pub struct ExportResult {
     pub success: bool,
     pub exported_count: usize,
+    /// Always `skipped.len()`.
     pub skipped_count: usize,
+    /// Every image left out by a filter or a failed read/copy, with the reason.
+    pub skipped: Vec<SkippedImage>,
     pub error: Option<String>,
     pub output_path: String,
+    /// Number of outputs renamed with a numeric suffix because the name was already taken.
+    pub collision_count: usize,
+    /// Exported count per subfolder (train/val) when the export was split.
+    pub splits: Vec<SplitCount>,
+    /// True if the export was stopped early by `cancel_export`.
+    pub cancelled: bool,
+    /// True if images were decoded and re-encoded (resize, conversion, grayscale, metadata strip)
+    /// instead of copied.
+    pub transformed: bool,
+    /// Relative paths left out by the resolution / tag-count filters or dedup_exact; included in
+    /// skipped_count.
+    pub excluded_paths: Vec<String>,
+    /// Images skipped by the walk as hidden or matching ignore_patterns.
+    pub ignored_count: usize,
+    /// Output names the export would write (dry_run only).
+    pub planned_names: Vec<String>,
+    /// Exported files that failed `verify` ("name: reason").
+    pub verification_errors: Vec<String>,
+    /// export_by_rating only: per-bucket totals (good, bad, needs_edit), for a composition
+    /// summary. The aggregate counts above still cover every bucket.
+    pub buckets: Vec<BucketCount>,
+    /// Images skipped by dedup_exact; included in skipped_count.
+    pub duplicate_count: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SkippedImage {
+    /// Relative to the source folder, forward slashes.
+    pub path: String,
+    pub reason: String,
+}
+
+impl ExportResult {
+    /// Put images dropped by the selection filters ahead of the export's own skips.
+    fn add_excluded(&mut self, excluded: Vec<SkippedImage>) {
+        self.excluded_paths = excluded.iter().map(|s| s.path.clone()).collect();
+        self.skipped.splice(0..0, excluded);
+        self.skipped_count = self.skipped.len();
+    }
+}
+
+impl SkippedImage {
+    fn new(img: &Path, source: &Path, reason: impl ToString) -> Self {
+        let rel = img.strip_prefix(source).unwrap_or(img);
+        Self {
+            path: normalize_rel(&rel.to_string_lossy()),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct BucketCount {
+    pub name: String,
+    pub exported: usize,
+    pub skipped: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SplitCount {
+    pub name: String,
+    pub count: usize,
+}
+
+/// Images exported under one subfolder of the export root (empty = the root itself).
+/// Sequential naming restarts at 1 within each group.
+struct ExportGroup {
+    subdir: String,
+    images: Vec<PathBuf>,
+}
+
+/// Deterministic Fisher–Yates shuffle driven by SplitMix64, so a seed gives the same order on
+/// every platform and release.
+fn seeded_shuffle<T>(items: &mut [T], seed: u64) {
+    let mut state = seed;
+    let mut next = || {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    };
+    for i in (1..items.len()).rev() {
+        let j = (next() % (i as u64 + 1)) as usize;
+        items.swap(i, j);
+    }
+}
+
+/// Parse an export format name into an image format.
+fn parse_convert_format(s: &str) -> Result<ImageFormat, CommandError> {
+    match s.trim().to_lowercase().as_str() {
+        "png" => Ok(ImageFormat::Png),
+        "jpg" | "jpeg" => Ok(ImageFormat::Jpeg),
+        "webp" => Ok(ImageFormat::WebP),
+        other => Err(CommandError::invalid(format!(
+            "Unsupported export format: {}",
+            other
+        ))),
+    }
+}
+
+fn convert_format(opt: &ExportOptions) -> Option<ImageFormat> {
+    opt.convert_format
+        .as_deref()
+        .and_then(|f| parse_convert_format(f).ok())
+}
+
+/// Whether images must be decoded and re-encoded rather than copied byte-for-byte.
+fn needs_transform(opt: &ExportOptions) -> bool {
+    !opt.captions_only
+        && (opt.max_side.is_some_and(|s| s > 0)
+            || convert_format(opt).is_some()
+            || opt.to_grayscale
+            || opt.strip_metadata)
 }
 
-/// Normalize relative path: forward slashes, trim leading slashes.
-fn normalize_rel(s: &str) -> String {
-    s.replace('\\', "/").trim_start_matches(|c| c == '/' || c == '\\').to_string()
+/// Caption file name for an exported image name: same stem, caption_ext's extension.
+fn caption_name(name: &str, opt: &ExportOptions) -> String {
+    let base = name.rsplit_once('.').map(|(n, _)| n).unwrap_or(name);
+    format!("{}.{}", base, opt.caption_ext.extension())
 }
 
-/// Normalize for case-insensitive path comparison (e.g. Windows).
-fn normalize_key_for_lookup(s: &str) -> String {
-    normalize_rel(s).to_lowercase()
+/// Extension of the exported image (the converted format's, or the original's).
+fn output_ext(img: &Path, opt: &ExportOptions) -> String {
+    match convert_format(opt).or_else(|| reencoded_as_png(img, opt).then_some(ImageFormat::Png)) {
+        Some(format) => format.extensions_str()[0].to_string(),
+        None => img
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("png")
+            .to_string(),
+    }
+}
+
+/// A re-encoded source whose format can't be written (HEIC, AVIF without an encoder) is exported
+/// as PNG.
+fn reencoded_as_png(img: &Path, opt: &ExportOptions) -> bool {
+    needs_transform(opt) && writable_format(img).is_none()
+}
+
+/// Decode, downscale, optionally grayscale and re-encode an image for export. Output format is the
+/// requested one, else the source's (PNG if it can't be written).
+fn transform_image(img: &Path, opt: &ExportOptions) -> Result<Vec<u8>, CommandError> {
+    let format = convert_format(opt)
+        .or_else(|| writable_format(img))
+        .unwrap_or(ImageFormat::Png);
+    // Re-encoding drops EXIF, so bake its orientation into the pixels to keep photos upright.
+    let (mut decoded, orientation) = open_oriented(img)?;
+    decoded.apply_orientation(orientation);
+    decoded = fit_longest_side(decoded, opt.max_side.unwrap_or(0));
+    if opt.to_grayscale {
+        decoded = decoded.grayscale();
+    }
+    if format == ImageFormat::Jpeg && decoded.color().has_alpha() {
+        // JPEG has no alpha channel.
+        decoded = if opt.to_grayscale {
+            image::DynamicImage::from(decoded.to_luma8())
+        } else {
+            image::DynamicImage::from(decoded.to_rgb8())
+        };
+    }
+    let mut buf = Vec::new();
+    match (format, opt.quality) {
+        (ImageFormat::Jpeg, Some(q)) => {
+            let encoder = JpegEncoder::new_with_quality(&mut buf, q.clamp(1, 100));
+            decoded.write_with_encoder(encoder)?;
+        }
+        _ => decoded.write_to(&mut Cursor::new(&mut buf), format)?,
+    }
+    Ok(buf)
+}
+
+/// only_captioned / only_uncaptioned selection on whether the caption file exists.
+fn passes_caption_filter(img: &Path, opt: &ExportOptions) -> bool {
+    if opt.only_captioned {
+        sidecar::existing(img).is_some()
+    } else if opt.only_uncaptioned {
+        sidecar::existing(img).is_none()
+    } else {
+        true
+    }
+}
+
+/// min_side / max_megapixels bounds, read from the header only. Unreadable images fail the
+/// filter when a bound is set.
+fn passes_resolution_filter(img: &Path, opt: &ExportOptions) -> bool {
+    if opt.min_side.is_none() && opt.max_megapixels.is_none() {
+        return true;
+    }
+    let Ok((w, h)) = image::image_dimensions(img) else {
+        return false;
+    };
+    let min_ok = opt.min_side.is_none_or(|min| w.min(h) >= min);
+    let max_ok = opt
+        .max_megapixels
+        .is_none_or(|max| (w as f64 * h as f64) / 1_000_000.0 <= max as f64);
+    min_ok && max_ok
+}
+
+/// min_tags / max_tags bounds on the caption's comma-separated tag count.
+fn passes_tag_count_filter(img: &Path, opt: &ExportOptions) -> bool {
+    if opt.min_tags.is_none() && opt.max_tags.is_none() {
+        return true;
+    }
+    let count = source_caption(img)
+        .map(|raw| parse_tags(&raw).len())
+        .unwrap_or(0);
+    opt.min_tags.is_none_or(|min| count >= min) && opt.max_tags.is_none_or(|max| count <= max)
+}
+
+/// `path` with `.` and `..` resolved lexically, since an export destination usually doesn't
+/// exist yet and can't be canonicalized.
+fn normalize_lexically(path: &Path) -> PathBuf {
+    let mut resolved = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            other => resolved.push(other),
+        }
+    }
+    resolved
+}
+
+/// Error if `dest` is the source folder or inside it: the export would copy files onto
+/// themselves, or write folders that the next walk of the source picks up as images. The
+/// nearest existing ancestor is canonicalized so symlinks are seen through.
+fn ensure_dest_outside_source(dest: &Path, canonical_source: &Path) -> Result<(), CommandError> {
+    let dest = normalize_lexically(&std::path::absolute(dest)?);
+    let mut existing = dest.as_path();
+    let mut missing = Vec::new();
+    let resolved = loop {
+        if let Ok(canonical) = existing.canonicalize() {
+            break missing.iter().rev().fold(canonical, |p, name| p.join(name));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                missing.push(name);
+                existing = parent;
+            }
+            _ => break dest.clone(),
+        }
+    };
+    if resolved.starts_with(canonical_source) {
+        return Err(CommandError::invalid(format!(
+            "Destination {} is inside the source folder {}; choose a folder outside it",
+            resolved.display(),
+            canonical_source.display()
+        )));
+    }
+    Ok(())
 }
 
 #[tauri::command]
-pub async fn export_dataset(options: ExportOptions) -> Result<ExportResult, String> {
+pub async fn export_dataset(
+    app: AppHandle,
+    mut options: ExportOptions,
+) -> Result<ExportResult, CommandError> {
     let source = PathBuf::from(&options.source_path);
     if !source.is_dir() {
-        return Err("Source folder does not exist".to_string());
+        return Err(CommandError::NotFound(
+            "Source folder does not exist".to_string(),
+        ));
+    }
+    if let Some(ref f) = options.convert_format {
+        parse_convert_format(f)?;
+    }
+    tag_separator(options.separator.as_deref())?;
+    if options.only_captioned && options.only_uncaptioned {
+        return Err(CommandError::invalid(
+            "only_captioned and only_uncaptioned cannot both be set",
+        ));
+    }
+    if options.val_split.is_some_and(|v| !(0.0..=1.0).contains(&v)) {
+        return Err(CommandError::invalid(
+            "val_split must be between 0.0 and 1.0",
+        ));
+    }
+    if let (Some(min), Some(max)) = (options.min_tags, options.max_tags) {
+        if min > max {
+            return Err(CommandError::invalid(
+                "min_tags cannot be greater than max_tags",
+            ));
+        }
     }
-    let canonical_source = source.canonicalize().map_err(|e| e.to_string())?;
+    let walker = ProjectWalk::new(options.follow_symlinks, options.ignore_patterns.as_deref())?;
+    let canonical_source = source.canonicalize()?;
+    if options.dest_relative_to_source {
+        let dest = normalize_lexically(&canonical_source.join(&options.dest_path));
+        options.dest_path = dest.to_string_lossy().to_string();
+    }
+    ensure_dest_outside_source(Path::new(&options.dest_path), &canonical_source)?;
+    EXPORT_CANCELLED.store(false, Ordering::Relaxed);
 
     let mut images: Vec<PathBuf> = Vec::new();
+    let mut ignored_count = 0;
 
     if let Some(ref relative_paths) = options.relative_paths {
         // Use frontend paths directly: join each to canonical source and add if file exists
@@ -77,136 +622,835 @@ pub async fn export_dataset(options: ExportOptions) -> Result<ExportResult, Stri
                 continue;
             }
             let full = canonical_source.join(&normalized);
-            if full.is_file() && is_image(&full) {
-                if options.only_captioned && !caption_path(&full).exists() {
+            if full.is_file() && is_image_path(&full) {
+                if !passes_caption_filter(&full, &options) {
                     continue;
                 }
                 images.push(full);
             }
         }
     } else {
-        // No filter: walk entire source and add all (subject to only_captioned)
-        for entry in WalkDir::new(&canonical_source).follow_links(false).into_iter().filter_map(Result::ok) {
-            let p = entry.path();
-            if !p.is_file() || !is_image(p) {
+        // No filter: walk entire source and add all (subject to only_captioned / only_uncaptioned)
+        let walked = walker.images(&canonical_source);
+        ignored_count = walked.ignored;
+        images = walked.images;
+        images.retain(|p| passes_caption_filter(p, &options));
+    }
+
+    images.sort();
+
+    let mut excluded: Vec<SkippedImage> = Vec::new();
+    images.retain(|img| {
+        let reason = if !passes_tag_count_filter(img, &options) {
+            "Tag count outside min_tags / max_tags"
+        } else if !passes_resolution_filter(img, &options) {
+            "Resolution outside min_side / max_megapixels"
+        } else {
+            return true;
+        };
+        excluded.push(SkippedImage::new(img, &canonical_source, reason));
+        false
+    });
+
+    let mut duplicate_count = 0;
+    if options.dedup_exact {
+        // Unreadable files are kept; the export itself reports why they fail.
+        let hashes: Vec<Option<Vec<u8>>> =
+            images.par_iter().map(|img| file_sha256(img).ok()).collect();
+        let mut seen = HashSet::new();
+        let mut unique = Vec::with_capacity(images.len());
+        for (img, hash) in images.into_iter().zip(hashes) {
+            if hash.is_some_and(|h| !seen.insert(h)) {
+                excluded.push(SkippedImage::new(&img, &canonical_source, "duplicate"));
+                duplicate_count += 1;
                 continue;
             }
-            if options.only_captioned && !caption_path(p).exists() {
+            unique.push(img);
+        }
+        images = unique;
+    }
+
+    let groups = match options.val_split {
+        Some(val_split) => {
+            seeded_shuffle(&mut images, options.split_seed.unwrap_or(0));
+            let train_len = ((1.0 - val_split as f64) * images.len() as f64).round() as usize;
+            let mut val = images.split_off(train_len.min(images.len()));
+            images.sort();
+            val.sort();
+            vec![
+                ExportGroup {
+                    subdir: "train".to_string(),
+                    images,
+                },
+                ExportGroup {
+                    subdir: "val".to_string(),
+                    images: val,
+                },
+            ]
+        }
+        None => vec![ExportGroup {
+            subdir: String::new(),
+            images,
+        }],
+    };
+    if options.sequential_naming {
+        let largest = groups.iter().map(|g| g.images.len()).max().unwrap_or(0);
+        check_pad_width(options.start_index, options.pad_width, largest)?;
+    }
+
+    if options.dry_run {
+        let mut result = plan_export(&groups, &canonical_source, &options);
+        result.add_excluded(excluded);
+        result.ignored_count = ignored_count;
+        result.duplicate_count = duplicate_count;
+        return Ok(result);
+    }
+
+    let total = groups.iter().map(|g| g.images.len()).sum();
+    let progress = ProgressReporter::new(&app, total);
+    let result = if options.as_zip {
+        export_archive(&groups, &canonical_source, &options, &progress)
+    } else {
+        export_folder(&groups, &canonical_source, &options, &progress)
+    };
+    progress.finish();
+    let mut r = result?;
+    r.add_excluded(excluded);
+    r.ignored_count = ignored_count;
+    r.duplicate_count = duplicate_count;
+    Ok(r)
+}
+
+/// Dry run: the names and counts export_folder / export_archive would produce, without writing.
+/// Files that would fail to read or decode can't be predicted and count as exported. With
+/// captions_only the planned names are the caption files and uncaptioned images are skipped.
+fn plan_export(groups: &[ExportGroup], source: &Path, opt: &ExportOptions) -> ExportResult {
+    let mut used_names: HashSet<String> = HashSet::new();
+    let mut collisions = 0usize;
+    let mut planned = Vec::new();
+    let mut skipped = Vec::new();
+    let mut group_counts = Vec::with_capacity(groups.len());
+    for group in groups {
+        let group_start = planned.len();
+        for (i, img) in group.images.iter().enumerate() {
+            // Named before the caption check, as the real exports do, so suffixes line up.
+            let name = in_subdir(&group.subdir, export_name(img, i, source, opt));
+            let (name, renamed) = unique_name(name, &mut used_names);
+            if renamed {
+                collisions += 1;
+            }
+            if opt.captions_only && source_caption(img).is_none() {
+                skipped.push(SkippedImage::new(img, source, "No caption"));
                 continue;
             }
-            images.push(p.to_path_buf());
+            planned.push(if opt.captions_only {
+                caption_name(&name, opt)
+            } else {
+                name
+            });
         }
+        group_counts.push(planned.len() - group_start);
+    }
+    let output_path = if opt.as_zip {
+        archive_path(&opt.dest_path, opt.archive_format)
+            .to_string_lossy()
+            .into_owned()
+    } else {
+        opt.dest_path.clone()
+    };
+    ExportResult {
+        success: true,
+        exported_count: planned.len(),
+        skipped_count: skipped.len(),
+        skipped,
+        error: None,
+        output_path,
+        collision_count: collisions,
+        splits: split_counts(groups, group_counts),
+        cancelled: false,
+        transformed: needs_transform(opt) && !planned.is_empty(),
+        excluded_paths: Vec::new(),
+        ignored_count: 0,
+        planned_names: planned,
+        verification_errors: Vec::new(),
+        buckets: Vec::new(),
+        duplicate_count: 0,
     }
+}
 
-    images.sort();
+/// Prefix an output name with its group's subfolder.
+fn in_subdir(subdir: &str, name: String) -> String {
+    if subdir.is_empty() {
+        name
+    } else {
+        format!("{}/{}", subdir, name)
+    }
+}
 
-    if options.as_zip {
-        export_zip(&images, &options)
+/// Per-group counts for the result; empty when the export wasn't split into subfolders.
+fn split_counts(groups: &[ExportGroup], counts: Vec<usize>) -> Vec<SplitCount> {
+    groups
+        .iter()
+        .zip(counts)
+        .filter(|(g, _)| !g.subdir.is_empty())
+        .map(|(g, count)| SplitCount {
+            name: g.subdir.clone(),
+            count,
+        })
+        .collect()
+}
+
+const DEFAULT_PAD_WIDTH: usize = 4;
+
+/// A file stem reduced to letters, digits, `-` and `_`; other runs become a single `-`.
+fn sanitize_stem(stem: &str) -> String {
+    let mut out = String::with_capacity(stem.len());
+    for c in stem.chars() {
+        if c.is_alphanumeric() || c == '_' || c == '-' {
+            out.push(c);
+        } else if !out.ends_with('-') {
+            out.push('-');
+        }
+    }
+    let out = out.trim_matches('-');
+    if out.is_empty() {
+        "image".to_string()
     } else {
-        export_folder(&images, &options)
+        out.to_string()
     }
 }
 
-fn apply_trigger(content: &str, trigger: Option<&String>) -> String {
-    let content = content.trim();
-    match trigger {
-        Some(t) if !t.is_empty() => format!("{}, {}", t.trim(), content),
-        _ => content.to_string(),
+/// Device names Windows reserves in any folder, with or without an extension.
+const WINDOWS_RESERVED: &[&str] = &[
+    "con", "prn", "aux", "nul", "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8",
+    "com9", "lpt1", "lpt2", "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9",
+];
+
+/// A file or folder name that is valid on Windows, macOS and Linux: characters illegal on any of
+/// them become `_`, whitespace runs collapse to one space, trailing dots/spaces are trimmed and
+/// reserved device names get a `_` suffix. Unlike sanitize_stem, readable names stay as they are.
+fn portable_name(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c.is_whitespace() {
+            if !out.ends_with(' ') {
+                out.push(' ');
+            }
+        } else if c.is_control()
+            || matches!(c, '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*')
+        {
+            out.push('_');
+        } else {
+            out.push(c);
+        }
+    }
+    let out = out.trim_start().trim_end_matches([' ', '.']);
+    if out.is_empty() {
+        return "image".to_string();
+    }
+    let device = out.split('.').next().unwrap_or(out).to_lowercase();
+    if WINDOWS_RESERVED.contains(&device.as_str()) {
+        format!("{}_", out)
+    } else {
+        out.to_string()
     }
 }
 
-fn export_folder(images: &[PathBuf], opt: &ExportOptions) -> Result<ExportResult, String> {
-    let dest = PathBuf::from(&opt.dest_path);
-    fs::create_dir_all(&dest).map_err(|e| e.to_string())?;
+/// Zero-padded sequential stem for the `index`-th (0-based) image of a group, optionally followed
+/// by the image's sanitized original stem.
+fn sequential_stem(
+    img: &Path,
+    index: usize,
+    start_index: Option<usize>,
+    pad_width: Option<usize>,
+    keep_name: bool,
+) -> String {
+    let n = start_index.unwrap_or(1) + index;
+    let number = format!(
+        "{:0width$}",
+        n,
+        width = pad_width.unwrap_or(DEFAULT_PAD_WIDTH)
+    );
+    if !keep_name {
+        return number;
+    }
+    let stem = img
+        .file_stem()
+        .map(|s| s.to_string_lossy())
+        .unwrap_or_default();
+    format!("{}_{}", number, sanitize_stem(&stem))
+}
 
-    let mut exported = 0usize;
-    let mut skipped = 0usize;
+/// Reject an explicit pad_width too narrow for the largest index in a group of `count` images;
+/// wider numbers would sort out of order.
+fn check_pad_width(
+    start_index: Option<usize>,
+    pad_width: Option<usize>,
+    count: usize,
+) -> Result<(), CommandError> {
+    let (Some(width), Some(last)) = (pad_width, count.checked_sub(1)) else {
+        return Ok(());
+    };
+    let max = start_index.unwrap_or(1) + last;
+    if max.to_string().len() > width {
+        return Err(CommandError::invalid(format!(
+            "pad_width {} is too small for index {}",
+            width, max
+        )));
+    }
+    Ok(())
+}
 
-    for (i, img) in images.iter().enumerate() {
-        let ext = img.extension().and_then(|e| e.to_str()).unwrap_or("png");
-        let name = if opt.sequential_naming {
-            format!("{:04}.{}", i + 1, ext)
-        } else {
-            img.file_name().and_then(|n| n.to_str()).unwrap_or("image.png").to_string()
+/// Output name for an image (relative to the export root, forward slashes).
+/// Sequential naming numbers from start_index (default 1); otherwise the original file name made
+/// portable (see portable_name), so the caption file follows the same name. With
+/// preserve_structure the image's subfolder (relative to source) is kept as a prefix.
+fn export_name(img: &Path, index: usize, source: &Path, opt: &ExportOptions) -> String {
+    let ext = output_ext(img, opt);
+    let name = if opt.sequential_naming {
+        let stem = sequential_stem(
+            img,
+            index,
+            opt.start_index,
+            opt.pad_width,
+            opt.sequential_keep_name,
+        );
+        format!("{}.{}", stem, ext)
+    } else {
+        let stem = img
+            .file_stem()
+            .map(|n| n.to_string_lossy())
+            .unwrap_or_default();
+        format!("{}.{}", portable_name(&stem), ext)
+    };
+    if !opt.preserve_structure {
+        return name;
+    }
+    let rel_dir = img
+        .parent()
+        .and_then(|p| p.strip_prefix(source).ok())
+        .map(|p| {
+            p.components()
+                .map(|c| portable_name(&c.as_os_str().to_string_lossy()))
+                .collect::<Vec<_>>()
+                .join("/")
+        })
+        .unwrap_or_default();
+    if rel_dir.is_empty() {
+        name
+    } else {
+        format!("{}/{}", rel_dir.trim_end_matches('/'), name)
+    }
+}
+
+/// Make `name` unique among `used` by appending `_1`, `_2`, ... before the extension.
+/// Compared by stem, case-insensitively: `a.png` and `a.jpg` would otherwise share `a.txt`, and
+/// Windows/macOS folders ignore case. Returns the name and whether it was changed.
+fn unique_name(name: String, used: &mut HashSet<String>) -> (String, bool) {
+    let (base, ext) = match name.rsplit_once('.') {
+        Some((b, e)) if !b.is_empty() && !b.ends_with('/') => (b.to_string(), format!(".{}", e)),
+        _ => (name.clone(), String::new()),
+    };
+    if used.insert(base.to_lowercase()) {
+        return (name, false);
+    }
+    let mut n = 1usize;
+    loop {
+        let candidate = format!("{}_{}", base, n);
+        if used.insert(candidate.to_lowercase()) {
+            return (format!("{}{}", candidate, ext), true);
+        }
+        n += 1;
+    }
+}
+
+/// Combine caption content with the trigger word at the requested position.
+/// Empty content yields just the trigger (no dangling comma).
+fn apply_trigger(content: &str, trigger: Option<&String>, position: TriggerPosition) -> String {
+    let content = content.trim().trim_matches(',').trim();
+    let trigger = match trigger.map(|t| t.trim()) {
+        Some(t) if !t.is_empty() => t,
+        _ => return content.to_string(),
+    };
+    if content.is_empty() {
+        return trigger.to_string();
+    }
+    match position {
+        TriggerPosition::Prefix => format!("{}, {}", trigger, content),
+        TriggerPosition::Suffix => format!("{}, {}", content, trigger),
+    }
+}
+
+/// Join prefix, content and suffix with ", ", skipping blank parts and stray edge commas so
+/// "best quality," + "" never leaves a dangling separator.
+fn wrap_caption(content: &str, prefix: Option<&str>, suffix: Option<&str>) -> String {
+    let trim = |s: &str| s.trim().trim_matches(',').trim().to_string();
+    [prefix.unwrap_or(""), content, suffix.unwrap_or("")]
+        .into_iter()
+        .map(trim)
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// FNV-1a, for per-file seeds that stay stable across platforms and Rust releases.
+fn stable_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |h, &b| {
+        (h ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Shuffle a caption's tags after the first `keep_leading`, seeded by the global seed and the
+/// image's file name so re-exports produce the same order.
+fn shuffle_caption(content: &str, img: &Path, opt: &ExportOptions) -> String {
+    let mut tags = parse_tags(content);
+    let name = img
+        .file_name()
+        .map(|n| n.to_string_lossy())
+        .unwrap_or_default();
+    let seed = opt.shuffle_seed.unwrap_or(0) ^ stable_hash(name.as_bytes());
+    let keep = opt.keep_leading.min(tags.len());
+    seeded_shuffle(&mut tags[keep..], seed);
+    tags.join(", ")
+}
+
+/// Caption text to export for an image (tags shuffled if requested, trigger applied, wrapped in
+/// caption_prefix / caption_suffix, then re-joined with separator if set), or None if it has no
+/// caption file.
+fn exported_caption(img: &Path, opt: &ExportOptions) -> Option<String> {
+    let mut content = source_caption(img)?;
+    if opt.shuffle_tags {
+        content = shuffle_caption(&content, img, opt);
+    }
+    let content = apply_trigger(&content, opt.trigger_word.as_ref(), opt.trigger_position);
+    let content = wrap_caption(
+        &content,
+        opt.caption_prefix.as_deref(),
+        opt.caption_suffix.as_deref(),
+    );
+    match opt.separator.as_deref() {
+        Some(separator) => Some(parse_tags(&content).join(separator)),
+        None => Some(content),
+    }
+}
+
+const MANIFEST_NAME: &str = "manifest.csv";
+
+/// One manifest.csv line: maps an exported file back to its source.
+struct ManifestRow {
+    source: String,
+    exported: String,
+    caption: String,
+    rating: Option<String>,
+}
+
+impl ManifestRow {
+    fn new(img: &Path, source: &Path, exported: &str, caption: Option<&str>) -> Self {
+        let rel = img.strip_prefix(source).unwrap_or(img);
+        ManifestRow {
+            source: normalize_rel(&rel.to_string_lossy()),
+            exported: exported.to_string(),
+            caption: caption.unwrap_or_default().to_string(),
+            rating: None,
+        }
+    }
+}
+
+/// Quote a CSV field when it contains a delimiter, quote or line break.
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+fn manifest_csv(rows: &[ManifestRow], with_rating: bool) -> String {
+    let mut out = String::from(if with_rating {
+        "source_path,exported_name,caption,rating\n"
+    } else {
+        "source_path,exported_name,caption\n"
+    });
+    for row in rows {
+        let mut fields = vec![
+            csv_field(&row.source),
+            csv_field(&row.exported),
+            csv_field(&row.caption),
+        ];
+        if with_rating {
+            fields.push(csv_field(row.rating.as_deref().unwrap_or("")));
+        }
+        out.push_str(&fields.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+fn file_sha256(path: &Path) -> Result<Vec<u8>, CommandError> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(hasher.finalize().to_vec())
+}
+
+/// Check an exported image: byte-identical for plain copies, decodable for re-encoded ones.
+fn verify_export(src: &Path, dest: &Path, transformed: bool) -> Result<(), CommandError> {
+    if transformed {
+        return image::open(dest)
+            .map(|_| ())
+            .map_err(|e| CommandError::from(e).context("output does not decode"));
+    }
+    if file_sha256(src)? != file_sha256(dest)? {
+        return Err(CommandError::Io("checksum mismatch".to_string()));
+    }
+    Ok(())
+}
+
+/// Write one exported image to `dest/name` (copied, or re-encoded when the options transform
+/// images) and its caption sidecar when there is one. Shared by folder and by-rating exports.
+fn write_export_entry(
+    img: &Path,
+    dest: &Path,
+    name: &str,
+    caption: Option<&str>,
+    opt: &ExportOptions,
+) -> Result<(), CommandError> {
+    let dest_img = dest.join(name);
+    if needs_transform(opt) {
+        let data = transform_image(img, opt)?;
+        fs::write(&dest_img, data)?;
+    } else {
+        fs::copy(img, &dest_img)?;
+    }
+    if let Some(caption) = caption {
+        let rendered = sidecar::render(opt.caption_ext, caption);
+        let _ = sidecar::write_atomic(&dest.join(caption_name(name, opt)), rendered.as_bytes());
+    }
+    Ok(())
+}
+
+/// One image of a folder export, named up front so sequential indices and collision suffixes
+/// don't depend on the order parallel copies finish in.
+struct FolderJob<'a> {
+    group: usize,
+    img: &'a Path,
+    name: String,
+}
+
+enum FolderOutcome {
+    /// Manifest row (when write_manifest is set) and verification error, if any.
+    Exported(Option<ManifestRow>, Option<String>),
+    Skipped(SkippedImage),
+    Cancelled,
+}
+
+fn export_folder_entry(
+    job: &FolderJob,
+    dest: &Path,
+    source: &Path,
+    opt: &ExportOptions,
+    progress: &ProgressReporter,
+) -> FolderOutcome {
+    if export_cancelled() {
+        return FolderOutcome::Cancelled;
+    }
+    let (img, name) = (job.img, job.name.as_str());
+    progress.start_file(img);
+    let dest_img = dest.join(name);
+    if let Some(parent) = dest_img.parent() {
+        if let Err(e) = fs::create_dir_all(parent) {
+            return FolderOutcome::Skipped(SkippedImage::new(
+                img,
+                source,
+                format!("Create folder: {}", e),
+            ));
+        }
+    }
+    if opt.captions_only {
+        let Some(caption) = exported_caption(img, opt) else {
+            return FolderOutcome::Skipped(SkippedImage::new(img, source, "No caption"));
         };
+        let txt_name = caption_name(name, opt);
+        let rendered = sidecar::render(opt.caption_ext, &caption);
+        if let Err(e) = sidecar::write_atomic(&dest.join(&txt_name), rendered.as_bytes()) {
+            return FolderOutcome::Skipped(SkippedImage::new(img, source, e.to_string()));
+        }
+        let row = opt
+            .write_manifest
+            .then(|| ManifestRow::new(img, source, &txt_name, Some(&caption)));
+        return FolderOutcome::Exported(row, None);
+    }
+    let caption = exported_caption(img, opt);
+    if let Err(e) = write_export_entry(img, dest, name, caption.as_deref(), opt) {
+        return FolderOutcome::Skipped(SkippedImage::new(img, source, e));
+    }
+    let verify_error = if opt.verify {
+        verify_export(img, &dest_img, needs_transform(opt))
+            .err()
+            .map(|e| format!("{}: {}", name, e))
+    } else {
+        None
+    };
+    let row = opt
+        .write_manifest
+        .then(|| ManifestRow::new(img, source, name, caption.as_deref()));
+    FolderOutcome::Exported(row, verify_error)
+}
 
-        let dest_img = dest.join(&name);
-        if fs::copy(img, &dest_img).is_err() {
-            skipped += 1;
-            continue;
+/// Names are assigned sequentially, then files are copied / re-encoded in parallel. Results are
+/// gathered in input order so skipped, manifest and verification lists stay deterministic.
+fn export_folder(
+    groups: &[ExportGroup],
+    source: &Path,
+    opt: &ExportOptions,
+    progress: &ProgressReporter,
+) -> Result<ExportResult, CommandError> {
+    let dest = PathBuf::from(&opt.dest_path);
+    fs::create_dir_all(&dest)?;
+
+    let mut collisions = 0usize;
+    let mut used_names: HashSet<String> = HashSet::new();
+    let mut jobs = Vec::new();
+    for (group_index, group) in groups.iter().enumerate() {
+        for (i, img) in group.images.iter().enumerate() {
+            let name = in_subdir(&group.subdir, export_name(img, i, source, opt));
+            let (name, renamed) = unique_name(name, &mut used_names);
+            if renamed {
+                collisions += 1;
+            }
+            jobs.push(FolderJob {
+                group: group_index,
+                img,
+                name,
+            });
         }
+    }
 
-        let base = name.rsplit_once('.').map(|(n, _)| n).unwrap_or(&name);
-        let dest_txt = dest.join(format!("{}.txt", base));
-        let cap_src = caption_path(img);
-        if cap_src.exists() {
-            if let Ok(content) = fs::read_to_string(&cap_src) {
-                let out = apply_trigger(&content, opt.trigger_word.as_ref());
-                let _ = fs::write(&dest_txt, out);
+    let outcomes: Vec<FolderOutcome> = jobs
+        .par_iter()
+        .map(|job| export_folder_entry(job, &dest, source, opt, progress))
+        .collect();
+
+    let mut exported = 0usize;
+    let mut skipped = Vec::new();
+    let mut group_counts = vec![0usize; groups.len()];
+    let mut cancelled = false;
+    let mut manifest: Vec<ManifestRow> = Vec::new();
+    let mut verification_errors = Vec::new();
+    for (job, outcome) in jobs.iter().zip(outcomes) {
+        match outcome {
+            FolderOutcome::Exported(row, verify_error) => {
+                exported += 1;
+                group_counts[job.group] += 1;
+                manifest.extend(row);
+                verification_errors.extend(verify_error);
             }
+            FolderOutcome::Skipped(s) => skipped.push(s),
+            FolderOutcome::Cancelled => cancelled = true,
         }
-        exported += 1;
+    }
+
+    if opt.write_manifest {
+        fs::write(dest.join(MANIFEST_NAME), manifest_csv(&manifest, false))?;
     }
 
     Ok(ExportResult {
         success: true,
         exported_count: exported,
-        skipped_count: skipped,
+        skipped_count: skipped.len(),
+        skipped,
         error: None,
         output_path: opt.dest_path.clone(),
+        collision_count: collisions,
+        splits: split_counts(groups, group_counts),
+        cancelled,
+        transformed: needs_transform(opt) && exported > 0,
+        excluded_paths: Vec::new(),
+        ignored_count: 0,
+        planned_names: Vec::new(),
+        verification_errors,
+        buckets: Vec::new(),
+        duplicate_count: 0,
     })
 }
 
-fn export_zip(images: &[PathBuf], opt: &ExportOptions) -> Result<ExportResult, String> {
-    use std::io::Write;
+/// Streaming archive writer; each entry goes straight to disk so memory stays bounded.
+enum ArchiveWriter {
+    Zip(zip::ZipWriter<fs::File>, Option<ZipCompression>),
+    Tar(tar::Builder<fs::File>),
+    TarGz(tar::Builder<GzEncoder<fs::File>>),
+}
+
+impl ArchiveWriter {
+    fn create(
+        path: &Path,
+        format: ArchiveFormat,
+        compression: Option<ZipCompression>,
+    ) -> Result<Self, CommandError> {
+        let file = fs::File::create(path)?;
+        Ok(match format {
+            ArchiveFormat::Zip => ArchiveWriter::Zip(zip::ZipWriter::new(file), compression),
+            ArchiveFormat::Tar => ArchiveWriter::Tar(tar::Builder::new(file)),
+            ArchiveFormat::TarGz => ArchiveWriter::TarGz(tar::Builder::new(GzEncoder::new(
+                file,
+                Compression::default(),
+            ))),
+        })
+    }
 
-    let file = fs::File::create(&opt.dest_path).map_err(|e| e.to_string())?;
-    let mut zip = zip::ZipWriter::new(file);
-    let opts = zip::write::SimpleFileOptions::default()
-        .compression_method(zip::CompressionMethod::Deflated);
+    fn add(&mut self, name: &str, data: &[u8]) -> Result<(), CommandError> {
+        use std::io::Write;
+
+        match self {
+            ArchiveWriter::Zip(zip, compression) => {
+                zip.start_file(name, zip_entry_options(name, *compression))?;
+                zip.write_all(data).map_err(CommandError::from)
+            }
+            ArchiveWriter::Tar(tar) => append_tar(tar, name, data),
+            ArchiveWriter::TarGz(tar) => append_tar(tar, name, data),
+        }
+    }
+
+    fn finish(self) -> Result<(), CommandError> {
+        match self {
+            ArchiveWriter::Zip(zip, _) => zip.finish().map(|_| ()).map_err(CommandError::from),
+            ArchiveWriter::Tar(tar) => tar.into_inner().map(|_| ()).map_err(CommandError::from),
+            ArchiveWriter::TarGz(tar) => tar
+                .into_inner()
+                .and_then(|gz| gz.finish())
+                .map(|_| ())
+                .map_err(CommandError::from),
+        }
+    }
+}
+
+fn append_tar<W: std::io::Write>(
+    tar: &mut tar::Builder<W>,
+    name: &str,
+    data: &[u8],
+) -> Result<(), CommandError> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+    );
+    tar.append_data(&mut header, name, data)
+        .map_err(CommandError::from)
+}
+
+/// dest_path with the archive's extension appended when it doesn't already end with it.
+fn archive_path(dest: &str, format: ArchiveFormat) -> PathBuf {
+    let ext = format.extension();
+    if dest.to_lowercase().ends_with(ext) {
+        PathBuf::from(dest)
+    } else {
+        PathBuf::from(format!("{}{}", dest, ext))
+    }
+}
+
+fn export_archive(
+    groups: &[ExportGroup],
+    source: &Path,
+    opt: &ExportOptions,
+    progress: &ProgressReporter,
+) -> Result<ExportResult, CommandError> {
+    let output_path = archive_path(&opt.dest_path, opt.archive_format);
+    let mut archive = ArchiveWriter::create(&output_path, opt.archive_format, opt.compression)?;
 
     let mut exported = 0usize;
-    let mut skipped = 0usize;
+    let mut skipped = Vec::new();
+    let mut collisions = 0usize;
+    // Duplicate entry names would be silently overwritten by most extractors.
+    let mut used_names: HashSet<String> = HashSet::new();
+    let mut group_counts = Vec::with_capacity(groups.len());
+    let mut cancelled = false;
+    let mut manifest: Vec<ManifestRow> = Vec::new();
 
-    for (i, img) in images.iter().enumerate() {
-        let ext = img.extension().and_then(|e| e.to_str()).unwrap_or("png");
-        let name = if opt.sequential_naming {
-            format!("{:04}.{}", i + 1, ext)
-        } else {
-            img.file_name().and_then(|n| n.to_str()).unwrap_or("image.png").to_string()
-        };
+    'groups: for group in groups {
+        let group_start = exported;
+        for (i, img) in group.images.iter().enumerate() {
+            if export_cancelled() {
+                cancelled = true;
+                group_counts.push(exported - group_start);
+                break 'groups;
+            }
+            progress.start_file(img);
+            let name = in_subdir(&group.subdir, export_name(img, i, source, opt));
+            let (name, renamed) = unique_name(name, &mut used_names);
+            if renamed {
+                collisions += 1;
+            }
 
-        let data = match fs::read(img) {
-            Ok(d) => d,
-            Err(_) => {
-                skipped += 1;
+            if opt.captions_only {
+                let Some(caption) = exported_caption(img, opt) else {
+                    skipped.push(SkippedImage::new(img, source, "No caption"));
+                    continue;
+                };
+                let txt_name = caption_name(&name, opt);
+                archive.add(
+                    &txt_name,
+                    sidecar::render(opt.caption_ext, &caption).as_bytes(),
+                )?;
+                if opt.write_manifest {
+                    manifest.push(ManifestRow::new(img, source, &txt_name, Some(&caption)));
+                }
+                exported += 1;
                 continue;
             }
-        };
-        zip.start_file(&name, opts).map_err(|e| e.to_string())?;
-        zip.write_all(&data).map_err(|e| e.to_string())?;
-
-        let base = name.rsplit_once('.').map(|(n, _)| n).unwrap_or(&name);
-        let txt_name = format!("{}.txt", base);
-        let cap_src = caption_path(img);
-        if cap_src.exists() {
-            if let Ok(content) = fs::read_to_string(&cap_src) {
-                let out = apply_trigger(&content, opt.trigger_word.as_ref());
-                zip.start_file(&txt_name, opts).map_err(|e| e.to_string())?;
-                zip.write_all(out.as_bytes()).map_err(|e| e.to_string())?;
+            let data = if needs_transform(opt) {
+                transform_image(img, opt)
+            } else {
+                fs::read(img).map_err(CommandError::from)
+            };
+            let data = match data {
+                Ok(d) => d,
+                Err(e) => {
+                    skipped.push(SkippedImage::new(img, source, e));
+                    continue;
+                }
+            };
+            archive.add(&name, &data)?;
+
+            let txt_name = caption_name(&name, opt);
+            let caption = exported_caption(img, opt);
+            if let Some(ref out) = caption {
+                archive.add(&txt_name, sidecar::render(opt.caption_ext, out).as_bytes())?;
             }
+            if opt.write_manifest {
+                manifest.push(ManifestRow::new(img, source, &name, caption.as_deref()));
+            }
+            exported += 1;
         }
-        exported += 1;
+        group_counts.push(exported - group_start);
+    }
+
+    if opt.write_manifest {
+        archive.add(MANIFEST_NAME, manifest_csv(&manifest, false).as_bytes())?;
     }
 
-    zip.finish().map_err(|e| e.to_string())?;
+    archive.finish()?;
 
     Ok(ExportResult {
         success: true,
         exported_count: exported,
-        skipped_count: skipped,
+        skipped_count: skipped.len(),
+        skipped,
         error: None,
-        output_path: opt.dest_path.clone(),
+        output_path: output_path.to_string_lossy().into_owned(),
+        collision_count: collisions,
+        splits: split_counts(groups, group_counts),
+        cancelled,
+        transformed: needs_transform(opt) && exported > 0,
+        excluded_paths: Vec::new(),
+        ignored_count: 0,
+        planned_names: Vec::new(),
+        verification_errors: Vec::new(),
+        buckets: Vec::new(),
+        duplicate_count: 0,
     })
 }
 
@@ -216,10 +1460,86 @@ fn export_zip(images: &[PathBuf], opt: &ExportOptions) -> Result<ExportResult, S
 pub struct ExportByRatingOptions {
     pub source_path: String,
     pub dest_path: String,
+    /// Descend into symlinked folders. WalkDir detects symlink cycles and skips the looping entry,
+    /// but links can still pull in large trees from outside the project.
+    #[serde(default)]
+    pub follow_symlinks: bool,
+    /// Globs (relative path, case-insensitive) of images to leave out, e.g. `raw/**`. Hidden
+    /// files and folders are always skipped.
+    #[serde(default)]
+    pub ignore_patterns: Option<Vec<String>>,
+    /// Global trigger word, used for buckets without an entry in `triggers`.
     #[serde(default)]
     pub trigger_word: Option<String>,
+    /// Per-bucket trigger words keyed by "good" / "bad" / "needs_edit". A bucket's entry overrides
+    /// `trigger_word`; mapping a bucket to "" exports its images without caption files.
+    #[serde(default)]
+    pub triggers: Option<HashMap<String, String>>,
+    #[serde(default)]
+    pub trigger_position: TriggerPosition,
+    /// Leave out images without a caption file.
+    #[serde(default)]
+    pub only_captioned: bool,
+    /// Downscale so the longest side is at most this many pixels (never upscales).
+    #[serde(default)]
+    pub max_side: Option<u32>,
+    /// Re-encode every image to this format: "png", "jpeg" or "webp".
+    #[serde(default)]
+    pub convert_format: Option<String>,
+    /// Export grayscale copies. Sources are left untouched.
+    #[serde(default)]
+    pub to_grayscale: bool,
+    /// Re-encode every image so only pixel data is written (drops EXIF/GPS/XMP).
+    #[serde(default)]
+    pub strip_metadata: bool,
+    /// JPEG quality 1–100 for re-encoded images (default 75).
+    #[serde(default)]
+    pub quality: Option<u8>,
     #[serde(default)]
     pub sequential_naming: bool,
+    /// First number for sequential_naming in each bucket (default 1).
+    #[serde(default)]
+    pub start_index: Option<usize>,
+    /// Zero-padded digits for sequential names (default 4). Must fit the largest index.
+    #[serde(default)]
+    pub pad_width: Option<usize>,
+    /// With sequential_naming, append the sanitized original stem: `0001_original-stem.png`.
+    #[serde(default)]
+    pub sequential_keep_name: bool,
+    /// Write manifest.csv (source path, exported name, caption, rating) at the export root.
+    #[serde(default)]
+    pub write_manifest: bool,
+    /// Report the planned bucket layout without writing anything.
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Sidecar format for exported captions (default .txt).
+    #[serde(default)]
+    pub caption_ext: CaptionExt,
+}
+
+impl ExportByRatingOptions {
+    /// Dataset export options for one bucket, so buckets are named, captioned and transformed by
+    /// the same code as export_dataset.
+    fn bucket_options(&self, trigger_word: Option<String>) -> ExportOptions {
+        ExportOptions {
+            source_path: self.source_path.clone(),
+            dest_path: self.dest_path.clone(),
+            only_captioned: self.only_captioned,
+            trigger_word,
+            trigger_position: self.trigger_position,
+            sequential_naming: self.sequential_naming,
+            start_index: self.start_index,
+            pad_width: self.pad_width,
+            sequential_keep_name: self.sequential_keep_name,
+            max_side: self.max_side,
+            convert_format: self.convert_format.clone(),
+            to_grayscale: self.to_grayscale,
+            strip_metadata: self.strip_metadata,
+            quality: self.quality,
+            caption_ext: self.caption_ext,
+            ..Default::default()
+        }
+    }
 }
 
 fn rating_key(r: ImageRating) -> Option<&'static str> {
@@ -231,58 +1551,30 @@ fn rating_key(r: ImageRating) -> Option<&'static str> {
     }
 }
 
-/// Look up rating for a relative path: try exact key, case-insensitive, then key as absolute path (strip project root).
-fn get_rating_for_path(
-    ratings: &RatingsData,
-    rel_key: &str,
-    rel: &str,
-    project_root: &str,
-) -> String {
-    if let Some(v) = ratings.ratings.get(rel_key) {
-        return v.clone();
-    }
-    if rel != rel_key {
-        if let Some(v) = ratings.ratings.get(rel) {
-            return v.clone();
-        }
-    }
-    let want = normalize_key_for_lookup(rel_key);
-    let root_norm = normalize_key_for_lookup(project_root);
-    for (k, v) in &ratings.ratings {
-        if normalize_key_for_lookup(k) == want {
-            return v.clone();
-        }
-        // Keys may have been stored as absolute paths if strip_prefix failed when project was opened
-        let k_norm = normalize_key_for_lookup(k);
-        if !root_norm.is_empty()
-            && k_norm.len() > root_norm.len()
-            && (k_norm.starts_with(&root_norm) || k_norm.starts_with(&root_norm.replace('\\', "/")))
-        {
-            let suffix = k_norm
-                .strip_prefix(&root_norm)
-                .or_else(|| k_norm.strip_prefix(&root_norm.replace('\\', "/")))
-                .unwrap_or(k_norm.as_str());
-            let suffix_trim = suffix.trim_start_matches(|c| c == '/' || c == '\\');
-            if !suffix_trim.is_empty() && normalize_key_for_lookup(suffix_trim) == want {
-                return v.clone();
-            }
-        }
-    }
-    "none".to_string()
-}
-
 #[tauri::command]
-pub async fn export_by_rating(options: ExportByRatingOptions) -> Result<ExportResult, String> {
+pub async fn export_by_rating(
+    app: AppHandle,
+    options: ExportByRatingOptions,
+) -> Result<ExportResult, CommandError> {
     let root = PathBuf::from(&options.source_path);
     if !root.is_dir() {
-        return Err("Source folder does not exist".to_string());
+        return Err(CommandError::NotFound(
+            "Source folder does not exist".to_string(),
+        ));
+    }
+    if let Some(ref f) = options.convert_format {
+        parse_convert_format(f)?;
     }
 
-    let canonical = root.canonicalize().map_err(|e| e.to_string())?;
+    let canonical = root.canonicalize()?;
+    // The good/bad/needs_edit folders inside the source would be re-walked as project images.
+    ensure_dest_outside_source(Path::new(&options.dest_path), &canonical)?;
+    EXPORT_CANCELLED.store(false, Ordering::Relaxed);
     let project_root = canonical.to_str().unwrap_or(options.source_path.as_str());
-    let ratings = load_ratings(project_root);
+    let ratings_data = load_ratings(project_root);
+    let ratings = RatingLookup::new(&ratings_data, project_root);
 
-    let mut by_rating: std::collections::HashMap<&'static str, Vec<PathBuf>> = [
+    let mut by_rating: HashMap<&'static str, Vec<PathBuf>> = [
         ("good", Vec::new()),
         ("bad", Vec::new()),
         ("needs_edit", Vec::new()),
@@ -291,11 +1583,9 @@ pub async fn export_by_rating(options: ExportByRatingOptions) -> Result<ExportRe
     .collect();
 
     // Walk from canonical so strip_prefix(canonical) always succeeds and matches how project stores relative_path.
-    for entry in WalkDir::new(&canonical).follow_links(false).into_iter().filter_map(Result::ok) {
-        let p = entry.path();
-        if !p.is_file() || !is_image(p) {
-            continue;
-        }
+    let walker = ProjectWalk::new(options.follow_symlinks, options.ignore_patterns.as_deref())?;
+    let walked = walker.images(&canonical);
+    for p in &walked.images {
         let rel = match p.strip_prefix(&canonical) {
             Ok(r) => r.to_str().map(|s| s.replace('\\', "/")).unwrap_or_default(),
             Err(_) => continue,
@@ -308,56 +1598,355 @@ pub async fn export_by_rating(options: ExportByRatingOptions) -> Result<ExportRe
             continue;
         }
 
-        let rating_str = get_rating_for_path(&ratings, &rel_key, &rel, project_root);
-        let rating = ImageRating::from_str(&rating_str);
+        if options.only_captioned && sidecar::existing(p).is_none() {
+            continue;
+        }
+
+        let rating = ImageRating::from_str(ratings.get(&rel_key, &rel));
         if let Some(key) = rating_key(rating) {
-            by_rating.get_mut(key).unwrap().push(p.to_path_buf());
+            by_rating.get_mut(key).unwrap().push(p.clone());
         }
     }
 
+    if options.sequential_naming {
+        let largest = by_rating.values().map(Vec::len).max().unwrap_or(0);
+        check_pad_width(options.start_index, options.pad_width, largest)?;
+    }
+
     let dest = PathBuf::from(&options.dest_path);
-    fs::create_dir_all(&dest).map_err(|e| e.to_string())?;
+    if !options.dry_run {
+        fs::create_dir_all(&dest)?;
+    }
 
+    let mut planned_names = Vec::new();
     let mut total_exported = 0usize;
-    let mut total_skipped = 0usize;
+    let mut skipped = Vec::new();
+    let mut collisions = 0usize;
+    let mut used_names: HashSet<String> = HashSet::new();
+    let mut cancelled = false;
+    let progress = ProgressReporter::new(&app, by_rating.values().map(Vec::len).sum());
+    let mut manifest: Vec<ManifestRow> = Vec::new();
+    let mut buckets: Vec<BucketCount> = Vec::new();
 
-    for (subdir, list) in by_rating.iter_mut() {
+    'buckets: for (subdir, list) in by_rating.iter_mut() {
+        let (exported_start, skipped_start) = (total_exported, skipped.len());
         list.sort();
         let sub = dest.join(*subdir);
-        fs::create_dir_all(&sub).map_err(|e| e.to_string())?;
+        if !options.dry_run {
+            fs::create_dir_all(&sub)?;
+        }
+        let bucket_trigger = options.triggers.as_ref().and_then(|t| t.get(*subdir));
+        let write_captions = bucket_trigger.is_none_or(|t| !t.is_empty());
+        let opt = options.bucket_options(bucket_trigger.or(options.trigger_word.as_ref()).cloned());
 
         for (i, img) in list.iter().enumerate() {
-            let ext = img.extension().and_then(|e| e.to_str()).unwrap_or("png");
-            let name = if options.sequential_naming {
-                format!("{:04}.{}", i + 1, ext)
+            if export_cancelled() {
+                cancelled = true;
+                buckets.push(BucketCount {
+                    name: subdir.to_string(),
+                    exported: total_exported - exported_start,
+                    skipped: skipped.len() - skipped_start,
+                });
+                break 'buckets;
+            }
+            progress.start_file(img);
+            // Folder names can repeat across subfolders; a second copy would overwrite the first.
+            let name = in_subdir(subdir, export_name(img, i, &canonical, &opt));
+            let (name, renamed) = unique_name(name, &mut used_names);
+            if renamed {
+                collisions += 1;
+            }
+            if options.dry_run {
+                planned_names.push(name);
+                total_exported += 1;
+                continue;
+            }
+
+            let caption = if write_captions {
+                exported_caption(img, &opt)
             } else {
-                img.file_name().and_then(|n| n.to_str()).unwrap_or("image.png").to_string()
+                None
             };
-
-            let dest_img = sub.join(&name);
-            if fs::copy(img, &dest_img).is_err() {
-                total_skipped += 1;
+            if let Err(e) = write_export_entry(img, &dest, &name, caption.as_deref(), &opt) {
+                skipped.push(SkippedImage::new(img, &canonical, e));
                 continue;
             }
-
-            let base = name.rsplit_once('.').map(|(n, _)| n).unwrap_or(&name);
-            let dest_txt = sub.join(format!("{}.txt", base));
-            let cap_src = caption_path(img);
-            if cap_src.exists() {
-                if let Ok(content) = fs::read_to_string(&cap_src) {
-                    let out = apply_trigger(&content, options.trigger_word.as_ref());
-                    let _ = fs::write(&dest_txt, out);
-                }
+            if options.write_manifest {
+                let mut row = ManifestRow::new(img, &canonical, &name, caption.as_deref());
+                row.rating = Some(subdir.to_string());
+                manifest.push(row);
             }
             total_exported += 1;
         }
+        buckets.push(BucketCount {
+            name: subdir.to_string(),
+            exported: total_exported - exported_start,
+            skipped: skipped.len() - skipped_start,
+        });
+    }
+    progress.finish();
+    let bucket_order = ["good", "bad", "needs_edit"];
+    buckets.sort_by_key(|b| bucket_order.iter().position(|n| *n == b.name));
+
+    if options.write_manifest && !options.dry_run {
+        fs::write(dest.join(MANIFEST_NAME), manifest_csv(&manifest, true))?;
     }
 
     Ok(ExportResult {
         success: true,
         exported_count: total_exported,
-        skipped_count: total_skipped,
+        skipped_count: skipped.len(),
+        skipped,
         error: None,
         output_path: options.dest_path.clone(),
+        collision_count: collisions,
+        splits: Vec::new(),
+        cancelled,
+        transformed: needs_transform(&options.bucket_options(None)) && total_exported > 0,
+        excluded_paths: Vec::new(),
+        ignored_count: walked.ignored,
+        planned_names,
+        verification_errors: Vec::new(),
+        buckets,
+        duplicate_count: 0,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trigger(content: &str, position: TriggerPosition) -> String {
+        apply_trigger(content, Some(&"ohwx".to_string()), position)
+    }
+
+    fn assert_no_edge_comma(caption: &str) {
+        let trimmed = caption.trim();
+        assert!(
+            !trimmed.starts_with(',') && !trimmed.ends_with(','),
+            "edge comma in {:?}",
+            caption
+        );
+    }
+
+    #[test]
+    fn trigger_prefix_with_content() {
+        let caption = trigger("1girl, red hair", TriggerPosition::Prefix);
+        assert_eq!(caption, "ohwx, 1girl, red hair");
+        assert_no_edge_comma(&caption);
+    }
+
+    #[test]
+    fn trigger_suffix_with_content() {
+        let caption = trigger("1girl, red hair", TriggerPosition::Suffix);
+        assert_eq!(caption, "1girl, red hair, ohwx");
+        assert_no_edge_comma(&caption);
+    }
+
+    #[test]
+    fn trigger_alone_when_content_is_empty() {
+        for position in [TriggerPosition::Prefix, TriggerPosition::Suffix] {
+            for content in ["", "   ", ","] {
+                let caption = trigger(content, position);
+                assert_eq!(caption, "ohwx", "{:?} {:?}", position, content);
+                assert_no_edge_comma(&caption);
+            }
+        }
+    }
+
+    #[test]
+    fn trigger_ignores_stray_commas_in_content() {
+        assert_eq!(
+            trigger("1girl, smile,", TriggerPosition::Suffix),
+            "1girl, smile, ohwx"
+        );
+        assert_eq!(trigger(", 1girl", TriggerPosition::Prefix), "ohwx, 1girl");
+    }
+
+    #[test]
+    fn blank_trigger_leaves_content() {
+        let blank = "  ".to_string();
+        assert_eq!(
+            apply_trigger(" 1girl ", Some(&blank), TriggerPosition::Prefix),
+            "1girl"
+        );
+        assert_eq!(
+            apply_trigger("1girl", None, TriggerPosition::Suffix),
+            "1girl"
+        );
+    }
+
+    /// Trigger, then prefix/suffix, as exported_caption applies them.
+    fn wrapped(content: &str, position: TriggerPosition, prefix: &str, suffix: &str) -> String {
+        wrap_caption(&trigger(content, position), Some(prefix), Some(suffix))
+    }
+
+    #[test]
+    fn wrap_with_trigger_prefix_and_suffix() {
+        let caption = wrapped("1girl", TriggerPosition::Prefix, "masterpiece", "high res");
+        assert_eq!(caption, "masterpiece, ohwx, 1girl, high res");
+        let caption = wrapped("1girl", TriggerPosition::Suffix, "masterpiece", "high res");
+        assert_eq!(caption, "masterpiece, 1girl, ohwx, high res");
+    }
+
+    #[test]
+    fn wrap_skips_empty_prefix_and_suffix() {
+        for (prefix, suffix, expected) in [
+            ("", "", "ohwx, 1girl"),
+            ("masterpiece", "", "masterpiece, ohwx, 1girl"),
+            ("", "high res", "ohwx, 1girl, high res"),
+            ("  ", " , ", "ohwx, 1girl"),
+        ] {
+            let caption = wrapped("1girl", TriggerPosition::Prefix, prefix, suffix);
+            assert_eq!(caption, expected, "{:?} {:?}", prefix, suffix);
+            assert_no_edge_comma(&caption);
+        }
+        assert_eq!(wrap_caption("1girl", None, None), "1girl");
+    }
+
+    #[test]
+    fn wrap_handles_parts_ending_in_commas() {
+        let caption = wrapped(
+            "1girl, smile,",
+            TriggerPosition::Suffix,
+            "best quality,",
+            ",",
+        );
+        assert_eq!(caption, "best quality, 1girl, smile, ohwx");
+        let caption = wrap_caption("1girl,", Some("best quality,"), Some("high res,"));
+        assert_eq!(caption, "best quality, 1girl, high res");
+    }
+
+    #[test]
+    fn wrap_empty_caption_keeps_only_fixed_text() {
+        let caption = wrap_caption("", Some("masterpiece"), Some("high res"));
+        assert_eq!(caption, "masterpiece, high res");
+        let caption = wrapped("", TriggerPosition::Suffix, "", "high res");
+        assert_eq!(caption, "ohwx, high res");
+    }
+
+    /// Source folder with two captioned PNGs, one uncaptioned PNG and one uncaptioned JPEG.
+    fn fixture() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let img = image::RgbImage::from_pixel(4, 4, image::Rgb([200, 10, 10]));
+        for name in ["a.png", "b.png", "c.png"] {
+            img.save(dir.path().join(name)).unwrap();
+        }
+        img.save(dir.path().join("d.jpg")).unwrap();
+        fs::write(dir.path().join("a.txt"), "1girl, smile").unwrap();
+        fs::write(dir.path().join("c.txt"), "1boy").unwrap();
+        dir
+    }
+
+    fn dry_run_and_export(opt: &ExportOptions) -> (ExportResult, ExportResult) {
+        let source = Path::new(&opt.source_path);
+        let mut images: Vec<PathBuf> = fs::read_dir(source)
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .filter(|p| is_image_path(p))
+            .collect();
+        images.sort();
+        let groups = vec![ExportGroup {
+            subdir: String::new(),
+            images,
+        }];
+        let planned = plan_export(&groups, source, opt);
+        let progress = ProgressReporter::silent(4);
+        let exported = export_folder(&groups, source, opt, &progress).unwrap();
+        (planned, exported)
+    }
+
+    fn assert_plan_matches(planned: &ExportResult, exported: &ExportResult) {
+        assert_eq!(planned.exported_count, exported.exported_count);
+        assert_eq!(planned.skipped_count, exported.skipped_count);
+        let reasons = |r: &ExportResult| {
+            r.skipped
+                .iter()
+                .map(|s| (s.path.clone(), s.reason.clone()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(reasons(planned), reasons(exported));
+        assert_eq!(planned.collision_count, exported.collision_count);
+    }
+
+    #[test]
+    fn dry_run_counts_match_captions_only_export() {
+        let source = fixture();
+        let dest = tempfile::tempdir().unwrap();
+        let opt = ExportOptions {
+            source_path: source.path().to_string_lossy().into_owned(),
+            dest_path: dest.path().to_string_lossy().into_owned(),
+            captions_only: true,
+            ..Default::default()
+        };
+        let (planned, exported) = dry_run_and_export(&opt);
+        assert_eq!(exported.exported_count, 2);
+        assert_eq!(exported.skipped_count, 2);
+        assert_plan_matches(&planned, &exported);
+        assert_eq!(planned.planned_names, vec!["a.txt", "c.txt"]);
+    }
+
+    #[test]
+    fn dry_run_counts_match_sequential_export() {
+        let source = fixture();
+        let dest = tempfile::tempdir().unwrap();
+        let opt = ExportOptions {
+            source_path: source.path().to_string_lossy().into_owned(),
+            dest_path: dest.path().to_string_lossy().into_owned(),
+            sequential_naming: true,
+            ..Default::default()
+        };
+        let (planned, exported) = dry_run_and_export(&opt);
+        assert_eq!(exported.exported_count, 4);
+        assert_plan_matches(&planned, &exported);
+        for name in &planned.planned_names {
+            assert!(
+                dest.path().join(name).is_file(),
+                "{} was not exported",
+                name
+            );
+        }
+    }
+
+    /// Folder export of 5000 images, timed with 1 thread and with larger pools. Copies are
+    /// I/O-bound, so extra threads help even past the core count. Run with
+    /// `cargo test --release export_folder_benchmark -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn export_folder_benchmark() {
+        const IMAGES: usize = 5000;
+        let source = tempfile::tempdir().unwrap();
+        let mut images = Vec::with_capacity(IMAGES);
+        for i in 0..IMAGES {
+            let img = source.path().join(format!("img_{:05}.png", i));
+            let bytes: Vec<u8> = (0..256 * 1024).map(|b| (b * 31 + i) as u8).collect();
+            fs::write(&img, bytes).unwrap();
+            fs::write(img.with_extension("txt"), "1girl, smile").unwrap();
+            images.push(img);
+        }
+        let groups = vec![ExportGroup {
+            subdir: String::new(),
+            images,
+        }];
+        for threads in [1, 4, 8] {
+            let dest = tempfile::tempdir().unwrap();
+            let opt = ExportOptions {
+                source_path: source.path().to_string_lossy().into_owned(),
+                dest_path: dest.path().to_string_lossy().into_owned(),
+                sequential_naming: true,
+                ..Default::default()
+            };
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            let progress = ProgressReporter::silent(IMAGES);
+            let start = std::time::Instant::now();
+            let result = pool
+                .install(|| export_folder(&groups, source.path(), &opt, &progress))
+                .unwrap();
+            assert_eq!(result.exported_count, IMAGES);
+            println!("{} thread(s): {:?}", threads, start.elapsed());
+        }
+    }
+}
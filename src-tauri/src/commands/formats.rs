@@ -0,0 +1,96 @@
+//! Image extensions recognised by project scans, walks and exports. Starts as the built-in list;
+//! `set_image_extensions` replaces it for the running app (e.g. to add `jfif` or drop `gif`).
+
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use std::path::Path;
+use std::sync::RwLock;
+
+use super::error::CommandError;
+
+/// HEIC/HEIF and AVIF are listed even when the decoders aren't compiled in, so those files still
+/// show up (and thumbnails explain what is missing).
+pub const DEFAULT_IMAGE_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "webp", "gif", "bmp", "heic", "heif", "avif",
+];
+
+/// Lowercase, without the leading dot.
+static IMAGE_EXTENSIONS: Lazy<RwLock<Vec<String>>> =
+    Lazy::new(|| RwLock::new(default_extensions()));
+
+fn default_extensions() -> Vec<String> {
+    DEFAULT_IMAGE_EXTENSIONS
+        .iter()
+        .map(|e| e.to_string())
+        .collect()
+}
+
+/// True if the path's extension is in the configured set (case-insensitive).
+pub fn is_image_path(path: &Path) -> bool {
+    let Some(ext) = path.extension().and_then(|e| e.to_str()) else {
+        return false;
+    };
+    let ext = ext.to_lowercase();
+    IMAGE_EXTENSIONS
+        .read()
+        .map(|exts| exts.contains(&ext))
+        .unwrap_or(false)
+}
+
+/// True if an image with the same stem sits next to `path` (e.g. `a.png` for `a.json`), in any
+/// configured extension, lowercase or uppercase.
+pub fn has_image_sibling(path: &Path) -> bool {
+    let Ok(exts) = IMAGE_EXTENSIONS.read() else {
+        return false;
+    };
+    exts.iter().any(|ext| {
+        path.with_extension(ext).is_file() || path.with_extension(ext.to_uppercase()).is_file()
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetImageExtensionsPayload {
+    /// Extensions with or without a leading dot. Unset or empty restores the defaults.
+    #[serde(default)]
+    pub extensions: Option<Vec<String>>,
+}
+
+/// Replace the recognised image extensions. Returns the normalized list now in effect.
+#[tauri::command]
+pub fn set_image_extensions(
+    payload: SetImageExtensionsPayload,
+) -> Result<Vec<String>, CommandError> {
+    let mut exts: Vec<String> = Vec::new();
+    for raw in payload.extensions.unwrap_or_default() {
+        let ext = raw.trim().trim_start_matches('.').to_lowercase();
+        if ext.is_empty() {
+            continue;
+        }
+        if !ext.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(CommandError::invalid(format!(
+                "Invalid image extension: {}",
+                raw
+            )));
+        }
+        if !exts.contains(&ext) {
+            exts.push(ext);
+        }
+    }
+    if exts.is_empty() {
+        exts = default_extensions();
+    }
+    let mut current = IMAGE_EXTENSIONS
+        .write()
+        .map_err(|e| CommandError::other(e.to_string()))?;
+    *current = exts.clone();
+    Ok(exts)
+}
+
+/// The image extensions currently recognised.
+#[tauri::command]
+pub fn get_image_extensions() -> Result<Vec<String>, CommandError> {
+    Ok(IMAGE_EXTENSIONS
+        .read()
+        .map_err(|e| CommandError::other(e.to_string()))?
+        .clone())
+}
@@ -1,33 +1,48 @@
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use image::codecs::gif::GifDecoder;
+use image::codecs::webp::WebPDecoder;
 use image::imageops::FilterType;
-use image::ImageFormat;
+use image::metadata::Orientation;
+use image::{AnimationDecoder, ImageDecoder, ImageFormat};
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::fs;
 use std::io::{Cursor, Read, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+use super::error::CommandError;
+use super::sidecar;
 
 const THUMB_SIZE: u32 = 256;
+/// Largest thumbnail size accepted; enough for 2x grid cells on hi-DPI screens. Bigger sizes cost
+/// more decode/encode time and a larger data URL over IPC, so request only what the grid shows.
+const THUMB_MAX_SIZE: u32 = 1024;
 const CACHE_DIR_NAME: &str = "lora-dataset-studio-thumbnails";
 
 /// Cache dir under temp. Creates on first use.
-fn thumbnail_cache_dir() -> Result<PathBuf, String> {
+fn thumbnail_cache_dir() -> Result<PathBuf, CommandError> {
     let dir = std::env::temp_dir().join(CACHE_DIR_NAME);
     if !dir.exists() {
-        fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        fs::create_dir_all(&dir)?;
     }
     Ok(dir)
 }
 
-/// Cache key from path and mtime so cache invalidates when file changes.
-fn thumbnail_cache_key(path: &std::path::Path, size: u32) -> Result<String, String> {
-    let meta = fs::metadata(path).map_err(|e| e.to_string())?;
+/// Cache key from path and mtime so cache invalidates when file changes. `frame`, a non-default
+/// `filter` and a `bounds` box are only hashed when set, so keys for plain thumbnails stay the same.
+fn thumbnail_cache_key(
+    path: &std::path::Path,
+    size: u32,
+    frame: Option<usize>,
+    filter: FilterType,
+    bounds: Option<ThumbnailBox>,
+) -> Result<String, CommandError> {
+    let meta = fs::metadata(path)?;
     let mtime = meta
-        .modified()
-        .map_err(|e| e.to_string())?
+        .modified()?
         .duration_since(std::time::UNIX_EPOCH)
-        .map_err(|_| "mtime error".to_string())?
+        .map_err(|_| CommandError::other("mtime error"))?
         .as_nanos()
         .to_string();
     let path_str = path.to_string_lossy();
@@ -35,200 +50,1512 @@ fn thumbnail_cache_key(path: &std::path::Path, size: u32) -> Result<String, Stri
     hasher.update(path_str.as_bytes());
     hasher.update(mtime.as_bytes());
     hasher.update(size.to_le_bytes());
+    if let Some(frame) = frame {
+        hasher.update((frame as u64).to_le_bytes());
+    }
+    if filter != FilterType::Triangle {
+        hasher.update(format!("{:?}", filter).as_bytes());
+    }
+    if let Some(b) = bounds {
+        hasher.update(b.width.to_le_bytes());
+        hasher.update(b.height.to_le_bytes());
+        hasher.update([b.fill as u8]);
+    }
     let hash = hasher.finalize();
     Ok(hex::encode(&hash[..16]))
 }
 
+fn has_extension(path: &Path, exts: &[&str]) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| exts.iter().any(|x| e.eq_ignore_ascii_case(x)))
+}
+
+/// Decode an image, falling back to libheif for HEIC/HEIF. AVIF decodes through `image` when
+/// built with the `avif` feature. Without the matching feature these report what is missing.
+pub fn open_image(path: &Path) -> Result<image::DynamicImage, CommandError> {
+    if has_extension(path, &["heic", "heif"]) {
+        return decode_heif(path);
+    }
+    match image::open(path) {
+        Err(image::ImageError::Unsupported(_))
+            if has_extension(path, &["avif"]) && !cfg!(feature = "avif") =>
+        {
+            Err(CommandError::NotImage(
+                "AVIF support is not compiled in (build with --features avif)".to_string(),
+            ))
+        }
+        result => Ok(result?),
+    }
+}
+
+/// Decode an image along with its EXIF orientation, which is not applied. libheif applies the
+/// HEIF rotation itself, so those decode upright and report NoTransforms.
+pub fn open_oriented(path: &Path) -> Result<(image::DynamicImage, Orientation), CommandError> {
+    if has_extension(path, &["heic", "heif"]) {
+        return Ok((open_image(path)?, Orientation::NoTransforms));
+    }
+    let mut decoder = image::ImageReader::open(path)
+        .and_then(|r| r.with_guessed_format())?
+        .into_decoder()?;
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    Ok((image::DynamicImage::from_decoder(decoder)?, orientation))
+}
+
+#[cfg(feature = "heic")]
+fn decode_heif(path: &Path) -> Result<image::DynamicImage, CommandError> {
+    use libheif_rs::{ColorSpace, HeifContext, LibHeif, RgbChroma};
+
+    let decode_err = |e: libheif_rs::HeifError| CommandError::Decode(e.to_string());
+    let path_str = path
+        .to_str()
+        .ok_or_else(|| CommandError::invalid("Path is not UTF-8"))?;
+    let ctx = HeifContext::read_from_file(path_str).map_err(decode_err)?;
+    let handle = ctx.primary_image_handle().map_err(decode_err)?;
+    let decoded = LibHeif::new()
+        .decode(&handle, ColorSpace::Rgb(RgbChroma::Rgba), None)
+        .map_err(decode_err)?;
+    let plane = decoded
+        .planes()
+        .interleaved
+        .ok_or_else(|| CommandError::Decode("HEIF image has no RGBA plane".to_string()))?;
+    let (width, height) = (plane.width, plane.height);
+    let row_len = width as usize * 4;
+    let mut pixels = Vec::with_capacity(row_len * height as usize);
+    for row in plane.data.chunks(plane.stride).take(height as usize) {
+        pixels.extend_from_slice(&row[..row_len]);
+    }
+    image::RgbaImage::from_raw(width, height, pixels)
+        .map(image::DynamicImage::from)
+        .ok_or_else(|| CommandError::Decode("HEIF pixel data has the wrong size".to_string()))
+}
+
+#[cfg(not(feature = "heic"))]
+fn decode_heif(_path: &Path) -> Result<image::DynamicImage, CommandError> {
+    Err(CommandError::NotImage(
+        "HEIC/HEIF support is not compiled in (build with --features heic)".to_string(),
+    ))
+}
+
+/// A JPEG's embedded EXIF thumbnail, when its longest side is at least `min_side` and it has the
+/// photo's aspect ratio (some cameras letterbox it). Reading it skips decoding the full photo.
+fn exif_thumbnail(path: &Path, min_side: u32) -> Option<image::DynamicImage> {
+    if !has_extension(path, &["jpg", "jpeg"]) {
+        return None;
+    }
+    let mut reader = std::io::BufReader::new(fs::File::open(path).ok()?);
+    let exif = exif::Reader::new().read_from_container(&mut reader).ok()?;
+    let field = |tag| {
+        exif.get_field(tag, exif::In::THUMBNAIL)
+            .and_then(|f| f.value.get_uint(0))
+    };
+    let offset = field(exif::Tag::JPEGInterchangeFormat)? as usize;
+    let len = field(exif::Tag::JPEGInterchangeFormatLength)? as usize;
+    let data = exif.buf().get(offset..offset.checked_add(len)?)?;
+    let thumb = image::load_from_memory_with_format(data, ImageFormat::Jpeg).ok()?;
+    let (tw, th) = (thumb.width(), thumb.height());
+    if tw.max(th) < min_side {
+        return None;
+    }
+    let (w, h) = image::image_dimensions(path).ok()?;
+    let aspect = |w: u32, h: u32| w as f64 / h.max(1) as f64;
+    ((aspect(tw, th) - aspect(w, h)).abs() / aspect(w, h) < 0.02).then_some(thumb)
+}
+
+/// Image to downscale for a `size` thumbnail: the EXIF thumbnail when it is big enough, otherwise
+/// the decoded file.
+fn thumbnail_source(path: &Path, size: u32) -> Result<image::DynamicImage, CommandError> {
+    match exif_thumbnail(path, size) {
+        Some(thumb) => Ok(thumb),
+        None => open_image(path),
+    }
+}
+
+/// Resize filter named in a payload: `nearest`, `triangle`, `catmull-rom`, `gaussian` or
+/// `lanczos3`. Unset uses `default`.
+fn parse_filter(value: Option<&str>, default: FilterType) -> Result<FilterType, CommandError> {
+    match value.map(|v| v.trim().to_ascii_lowercase()).as_deref() {
+        None | Some("") => Ok(default),
+        Some("nearest") => Ok(FilterType::Nearest),
+        Some("triangle") => Ok(FilterType::Triangle),
+        Some("catmull-rom") | Some("catmullrom") => Ok(FilterType::CatmullRom),
+        Some("gaussian") => Ok(FilterType::Gaussian),
+        Some("lanczos3") => Ok(FilterType::Lanczos3),
+        Some(other) => Err(CommandError::invalid(format!(
+            "Unknown resize filter: {} (expected nearest, triangle, catmull-rom, gaussian or \
+             lanczos3)",
+            other
+        ))),
+    }
+}
+
+/// Downscale so the longest side is at most `max_side` (0 = unchanged). Never upscales.
+pub fn fit_longest_side(img: image::DynamicImage, max_side: u32) -> image::DynamicImage {
+    fit_longest_side_with(img, max_side, FilterType::Triangle)
+}
+
+fn fit_longest_side_with(
+    img: image::DynamicImage,
+    max_side: u32,
+    filter: FilterType,
+) -> image::DynamicImage {
+    let (w, h) = (img.width(), img.height());
+    let longest = w.max(h);
+    if max_side == 0 || longest <= max_side {
+        return img;
+    }
+    let scale = max_side as f32 / longest as f32;
+    let new_w = ((w as f32 * scale).round() as u32).max(1);
+    let new_h = ((h as f32 * scale).round() as u32).max(1);
+    img.resize(new_w, new_h, filter)
+}
+
+/// Create the first free `{stem}_{n}_{tag}.{ext}` next to `path` (n = 1..9999), for "save as new"
+/// outputs. Uses create_new, so a file another operation creates meanwhile is never overwritten;
+/// that name is skipped instead.
+pub fn create_unique_sibling(path: &Path, tag: &str) -> Result<(PathBuf, fs::File), CommandError> {
+    let parent = path.parent().unwrap_or(path);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("image");
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("png");
+    for n in 1..=9999u32 {
+        let candidate = parent.join(format!("{}_{}_{}.{}", stem, n, tag, ext));
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&candidate)
+        {
+            Ok(file) => return Ok((candidate, file)),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Err(CommandError::Io(
+        "Could not create unique filename for new image".to_string(),
+    ))
+}
+
+/// Encode `img` to `path`, dropping alpha for formats that can't store it (JPEG).
+fn write_image(
+    img: &image::DynamicImage,
+    path: &Path,
+    format: ImageFormat,
+) -> Result<(), CommandError> {
+    write_image_to(img, fs::File::create(path)?, format)
+}
+
+fn write_image_to(
+    img: &image::DynamicImage,
+    file: fs::File,
+    format: ImageFormat,
+) -> Result<(), CommandError> {
+    let mut file = std::io::BufWriter::new(file);
+    if format == ImageFormat::Jpeg && img.color().has_alpha() {
+        image::DynamicImage::from(img.to_rgb8())
+            .write_to(&mut file, format)
+            .map_err(CommandError::from)
+    } else {
+        img.write_to(&mut file, format).map_err(CommandError::from)
+    }
+}
+
+/// The source's format if it can also be written. HEIC (and AVIF without an encoder) can be read
+/// but not written, so edits and re-encoded exports of those become PNGs.
+pub fn writable_format(path: &Path) -> Option<ImageFormat> {
+    ImageFormat::from_path(path)
+        .ok()
+        .filter(|f| f.writing_enabled())
+}
+
+/// Write an edited image over the original, or next to it as `{stem}_{n}_{tag}` (with the caption
+/// copied when with_caption is set). Keeps the source format, or writes a PNG when it can't be
+/// written (see writable_format). Returns the new path when save_as_new is true.
+fn save_edited(
+    img: &image::DynamicImage,
+    path: &Path,
+    tag: &str,
+    save_as_new: bool,
+    with_caption: bool,
+) -> Result<Option<String>, CommandError> {
+    let writable = writable_format(path);
+    if !save_as_new {
+        let Some(format) = writable else {
+            return Err(CommandError::invalid(
+                "This format can't be written in place; save as new to get a PNG",
+            ));
+        };
+        write_image(img, path, format)?;
+        return Ok(None);
+    }
+    let base = if writable.is_some() {
+        path.to_path_buf()
+    } else {
+        path.with_extension("png")
+    };
+    let (out_path, file) = create_unique_sibling(&base, tag)?;
+    if let Err(e) = write_image_to(img, file, writable.unwrap_or(ImageFormat::Png)) {
+        let _ = fs::remove_file(&out_path);
+        return Err(e);
+    }
+    if with_caption {
+        copy_caption(path, &out_path);
+    }
+    Ok(Some(out_path.to_string_lossy().into_owned()))
+}
+
+/// Copy an image's caption to another image as the same sidecar kind, trimmed. Returns whether a
+/// caption was written (false if the source has none).
+fn copy_caption(from_image: &Path, to_image: &Path) -> bool {
+    let Some(caption_path) = sidecar::existing(from_image) else {
+        return false;
+    };
+    let ext = caption_path.extension().unwrap_or_default();
+    match sidecar::read(&caption_path) {
+        Ok(content) => sidecar::write(&to_image.with_extension(ext), content.trim()).is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// Sibling backup path for an in-place edit: `photo.png` -> `photo.png.bak`.
+fn backup_path(path: &Path) -> PathBuf {
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("image");
+    path.with_file_name(format!("{}.bak", name))
+}
+
+/// Copy the original next to itself before it is overwritten. Keeps the first backup so repeated
+/// edits can still be rolled back to the untouched file.
+fn backup_original(path: &Path) -> Result<(), CommandError> {
+    let backup = backup_path(path);
+    if !backup.exists() {
+        fs::copy(path, &backup)
+            .map_err(|e| CommandError::from(e).context("Failed to back up original"))?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CropImagePayload {
+    pub image_path: String,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    #[serde(default)]
+    pub flip_x: bool,
+    #[serde(default)]
+    pub flip_y: bool,
+    #[serde(default)]
+    pub rotate_degrees: i32,
+    /// If true, save cropped image to a new file (keeps original). Returns new path.
+    #[serde(default)]
+    pub save_as_new: bool,
+    /// If set, resize output to this size (square) for LoRA/training (e.g. 512 or 1024).
+    #[serde(default)]
+    pub output_size: Option<u32>,
+    /// When overwriting in place, first copy the original to `<file>.bak` (see restore_original).
+    #[serde(default)]
+    pub auto_backup: bool,
+    /// With save_as_new, copy the source caption to the new image (default true).
+    #[serde(default = "default_true")]
+    pub copy_caption: bool,
+    /// Size of the preview the rectangle was drawn on. When the image has an EXIF orientation,
+    /// the preview was shown upright and the rectangle is mapped back into raw pixel coordinates.
+    #[serde(default)]
+    pub display_width: Option<u32>,
+    #[serde(default)]
+    pub display_height: Option<u32>,
+    /// Shrink the rectangle (keeping its origin) to the largest one of this `[w, h]` ratio that
+    /// fits both the request and the image.
+    #[serde(default)]
+    pub lock_aspect: Option<(u32, u32)>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Map a rectangle drawn on the EXIF-oriented preview back into raw (stored) pixel coordinates.
+/// Errors if the display size doesn't match the oriented image or the rectangle falls outside it.
+fn remap_oriented_rect(
+    (x, y, width, height): (u32, u32, u32, u32),
+    (dw, dh): (u32, u32),
+    (w, h): (u32, u32),
+    orientation: Orientation,
+) -> Result<(u32, u32, u32, u32), CommandError> {
+    let swaps = matches!(
+        orientation,
+        Orientation::Rotate90
+            | Orientation::Rotate270
+            | Orientation::Rotate90FlipH
+            | Orientation::Rotate270FlipH
+    );
+    let oriented = if swaps { (h, w) } else { (w, h) };
+    if (dw, dh) != oriented {
+        return Err(CommandError::InvalidInput(format!(
+            "Displayed size {}x{} does not match image size {}x{}",
+            dw, dh, oriented.0, oriented.1
+        )));
+    }
+    let (x0, y0) = (x as u64, y as u64);
+    let (x1, y1) = (x0 + width as u64, y0 + height as u64);
+    if x1 > dw as u64 || y1 > dh as u64 {
+        return Err(CommandError::invalid("Crop region exceeds image bounds"));
+    }
+
+    // Inverse of each orientation, on continuous edge coordinates of the displayed image.
+    let (w64, h64) = (w as u64, h as u64);
+    let to_raw = |dx: u64, dy: u64| -> (u64, u64) {
+        match orientation {
+            Orientation::NoTransforms => (dx, dy),
+            Orientation::Rotate90 => (dy, h64 - dx),
+            Orientation::Rotate180 => (w64 - dx, h64 - dy),
+            Orientation::Rotate270 => (w64 - dy, dx),
+            Orientation::FlipHorizontal => (w64 - dx, dy),
+            Orientation::FlipVertical => (dx, h64 - dy),
+            Orientation::Rotate90FlipH => (dy, dx),
+            Orientation::Rotate270FlipH => (w64 - dy, h64 - dx),
+        }
+    };
+    let (ax, ay) = to_raw(x0, y0);
+    let (bx, by) = to_raw(x1, y1);
+    let (rx, ry) = (ax.min(bx), ay.min(by));
+    let (rw, rh) = (ax.abs_diff(bx), ay.abs_diff(by));
+    if rx + rw > w64 || ry + rh > h64 {
+        return Err(CommandError::invalid("Crop region exceeds image bounds"));
+    }
+    Ok((rx as u32, ry as u32, rw as u32, rh as u32))
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// Largest rectangle of exactly `aw:ah` at the same origin that is no larger than the request and
+/// stays within `limit`. Never grows the request.
+fn snap_to_aspect(
+    (x, y, width, height): (u32, u32, u32, u32),
+    (aw, ah): (u32, u32),
+    (lw, lh): (u32, u32),
+) -> Result<(u32, u32, u32, u32), CommandError> {
+    if aw == 0 || ah == 0 {
+        return Err(CommandError::invalid("Aspect ratio must be non-zero"));
+    }
+    let g = gcd(aw, ah);
+    let (rw, rh) = (aw / g, ah / g);
+    let max_w = width.min(lw.saturating_sub(x));
+    let max_h = height.min(lh.saturating_sub(y));
+    let k = (max_w / rw).min(max_h / rh);
+    if k == 0 {
+        return Err(CommandError::InvalidInput(format!(
+            "Crop region is too small for a {}:{} ratio",
+            aw, ah
+        )));
+    }
+    Ok((x, y, rw * k, rh * k))
+}
+
+#[derive(Debug, Serialize)]
+pub struct CropBounds {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CropImageResult {
+    /// Path of the new file when save_as_new is true.
+    pub new_path: Option<String>,
+    pub caption_copied: bool,
+    /// Rectangle actually cropped, in the coordinates of the request (after clamping and
+    /// lock_aspect).
+    pub bounds: CropBounds,
+    /// Size of the written image after crop, flip, rotate and output_size.
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetThumbnailPayload {
+    pub path: String,
+    /// Longest side in pixels (default 256, capped at THUMB_MAX_SIZE).
+    #[serde(default)]
+    pub size: Option<u32>,
+    /// "jpeg" (default) or "webp" (smaller data URLs).
+    #[serde(default)]
+    pub format: Option<String>,
+    /// For animated GIF/WebP, the 0-based frame to show (clamped to the last frame). Unset means
+    /// the first frame.
+    #[serde(default)]
+    pub animated_frame: Option<usize>,
+    /// Resize filter (see parse_filter); default triangle, which is fast but slightly soft.
+    #[serde(default)]
+    pub filter: Option<String>,
+    /// When both are set, fit the thumbnail in this box (each side capped at THUMB_MAX_SIZE)
+    /// instead of a `size` square.
+    #[serde(default)]
+    pub width: Option<u32>,
+    #[serde(default)]
+    pub height: Option<u32>,
+    /// With width/height, scale and center-crop to fill the box exactly (uniform grid cells).
+    #[serde(default)]
+    pub fill: bool,
+}
+
+/// Non-square thumbnail bounds from width/height.
+#[derive(Debug, Clone, Copy)]
+struct ThumbnailBox {
+    width: u32,
+    height: u32,
+    fill: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Thumbnail {
+    pub data_url: String,
+    /// Size of the encoded thumbnail.
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Thumbnail {
+    fn new(format: PreviewFormat, buf: &[u8], (width, height): (u32, u32)) -> Self {
+        Self {
+            data_url: format.data_url(buf),
+            width,
+            height,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetImageDataUrlPayload {
+    pub path: String,
+    /// Max length of the longest side (for preview); 0 = full size.
+    #[serde(default)]
+    pub max_side: Option<u32>,
+    /// "jpeg" (default) or "webp".
+    #[serde(default)]
+    pub format: Option<String>,
+    /// Resize filter (see parse_filter); default lanczos3 for sharp previews.
+    #[serde(default)]
+    pub filter: Option<String>,
+}
+
+const WEBP_QUALITY: f32 = 80.0;
+
+/// Encoding used for thumbnail/preview data URLs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PreviewFormat {
+    Jpeg,
+    Webp,
+}
+
+impl PreviewFormat {
+    fn parse(value: Option<&str>) -> Result<Self, CommandError> {
+        match value.map(|v| v.trim().to_ascii_lowercase()).as_deref() {
+            None | Some("") | Some("jpeg") | Some("jpg") => Ok(Self::Jpeg),
+            Some("webp") => Ok(Self::Webp),
+            Some(other) => Err(CommandError::InvalidInput(format!(
+                "Unsupported preview format: {}",
+                other
+            ))),
+        }
+    }
+
+    fn mime(self) -> &'static str {
+        match self {
+            Self::Jpeg => "image/jpeg",
+            Self::Webp => "image/webp",
+        }
+    }
+
+    fn cache_ext(self) -> &'static str {
+        match self {
+            Self::Jpeg => "jpg",
+            Self::Webp => "webp",
+        }
+    }
+
+    /// Encode for preview. WebP goes through libwebp because image's encoder is lossless-only,
+    /// which would be larger than JPEG for photos.
+    fn encode(self, img: &image::DynamicImage) -> Result<Vec<u8>, CommandError> {
+        match self {
+            Self::Jpeg => {
+                let mut buf = Vec::new();
+                img.write_to(&mut Cursor::new(&mut buf), ImageFormat::Jpeg)?;
+                Ok(buf)
+            }
+            Self::Webp => {
+                let img = if img.color().has_alpha() {
+                    image::DynamicImage::from(img.to_rgba8())
+                } else {
+                    image::DynamicImage::from(img.to_rgb8())
+                };
+                let encoder = webp::Encoder::from_image(&img).map_err(CommandError::other)?;
+                Ok(encoder.encode(WEBP_QUALITY).to_vec())
+            }
+        }
+    }
+
+    fn data_url(self, buf: &[u8]) -> String {
+        format!("data:{};base64,{}", self.mime(), BASE64.encode(buf))
+    }
+}
+
+/// Generates a thumbnail for the image at path. Returns a data URL (base64 JPEG or WebP) and its
+/// pixel size.
+/// Uses an on-disk cache under temp (keyed by path + mtime + size) to avoid regenerating on scroll.
+/// Animated GIF/WebP files show their first frame unless `animated_frame` picks another. JPEGs
+/// with a large enough embedded EXIF thumbnail are served from it without a full decode.
+#[tauri::command]
+pub fn get_thumbnail(payload: GetThumbnailPayload) -> Result<Thumbnail, CommandError> {
+    let path = PathBuf::from(&payload.path);
+    if !path.exists() || !path.is_file() {
+        return Err(CommandError::NotFound("File not found".to_string()));
+    }
+
+    let format = PreviewFormat::parse(payload.format.as_deref())?;
+    let filter = parse_filter(payload.filter.as_deref(), FilterType::Triangle)?;
+    let size = payload.size.unwrap_or(THUMB_SIZE).min(THUMB_MAX_SIZE);
+    let bounds = match (payload.width, payload.height) {
+        (Some(w), Some(h)) if w > 0 && h > 0 => Some(ThumbnailBox {
+            width: w.min(THUMB_MAX_SIZE),
+            height: h.min(THUMB_MAX_SIZE),
+            fill: payload.fill,
+        }),
+        _ => None,
+    };
+    let cache_dir = thumbnail_cache_dir()?;
+    let key = thumbnail_cache_key(&path, size, payload.animated_frame, filter, bounds)?;
+    let cache_path = cache_dir.join(format!("{}.{}", key, format.cache_ext()));
+
+    if cache_path.exists() && cache_path.is_file() {
+        let mut buf = Vec::new();
+        let mut f = fs::File::open(&cache_path)?;
+        f.read_to_end(&mut buf)?;
+        let dims = image::ImageReader::new(Cursor::new(&buf))
+            .with_guessed_format()?
+            .into_dimensions()?;
+        return Ok(Thumbnail::new(format, &buf, dims));
+    }
+
+    let needed = bounds.map_or(size, |b| b.width.max(b.height));
+    let img = match payload.animated_frame {
+        Some(frame) => open_frame(&path, frame)?,
+        None => thumbnail_source(&path, needed)?,
+    };
+    let thumb = match bounds {
+        Some(b) if b.fill => img.resize_to_fill(b.width, b.height, filter),
+        Some(b) => img.resize(b.width, b.height, filter),
+        None => img.resize(size, size, filter),
+    };
+    let buf = format.encode(&thumb)?;
+
+    if let Ok(mut f) = fs::File::create(&cache_path) {
+        let _ = f.write_all(&buf);
+    }
+
+    Ok(Thumbnail::new(
+        format,
+        &buf,
+        (thumb.width(), thumb.height()),
+    ))
+}
+
+/// Frames of an animated GIF or WebP. `None` for other formats and still WebP files; a GIF is
+/// always returned, even with a single frame.
+fn animation_frames(path: &Path) -> Result<Option<image::Frames<'static>>, CommandError> {
+    let reader = || {
+        fs::File::open(path)
+            .map(std::io::BufReader::new)
+            .map_err(CommandError::from)
+    };
+    match ImageFormat::from_path(path) {
+        Ok(ImageFormat::Gif) => {
+            let decoder = GifDecoder::new(reader()?)?;
+            Ok(Some(decoder.into_frames()))
+        }
+        Ok(ImageFormat::WebP) => {
+            let decoder = WebPDecoder::new(reader()?)?;
+            Ok(decoder.has_animation().then(|| decoder.into_frames()))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Decode one frame of an animated image (clamped to the last frame); still images decode as-is.
+fn open_frame(path: &Path, frame: usize) -> Result<image::DynamicImage, CommandError> {
+    let Some(frames) = animation_frames(path)? else {
+        return open_image(path);
+    };
+    let mut picked = None;
+    for (i, f) in frames.enumerate() {
+        picked = Some(f?);
+        if i == frame {
+            break;
+        }
+    }
+    picked
+        .map(|f| image::DynamicImage::from(f.into_buffer()))
+        .ok_or_else(|| CommandError::Decode("Animation has no frames".to_string()))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetImageInfoPayload {
+    pub path: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImageInfo {
+    pub width: u32,
+    pub height: u32,
+    /// Lowercase format name from the extension (e.g. "png", "gif"), if recognised.
+    pub format: Option<String>,
+    pub file_size: u64,
+    pub is_animated: bool,
+    /// Frame count for GIF and animated WebP; `None` for other images.
+    pub frame_count: Option<usize>,
+    /// False if the full image fails to decode (truncated download, corrupt data).
+    pub is_valid: bool,
+    /// Decode error when is_valid is false.
+    pub error: Option<String>,
+}
+
+/// Decode the whole image to catch truncated or corrupt files that still have a readable header.
+pub fn validate_image(path: &Path) -> Result<(), CommandError> {
+    open_image(path).map(|_| ())
+}
+
+/// Dimensions, format, animation info and validity for one image. Validation and counting frames
+/// decode the whole file, so this is meant for a single selected image rather than the grid.
+#[tauri::command]
+pub fn get_image_info(payload: GetImageInfoPayload) -> Result<ImageInfo, CommandError> {
+    let path = PathBuf::from(&payload.path);
+    if !path.is_file() {
+        return Err(CommandError::NotFound("File not found".to_string()));
+    }
+    let file_size = fs::metadata(&path)?.len();
+    let (width, height) = match image::image_dimensions(&path) {
+        Ok(dims) => dims,
+        Err(_) => open_image(&path).map(|img| (img.width(), img.height()))?,
+    };
+    let format = match ImageFormat::from_path(&path) {
+        Ok(f) => f.extensions_str().first().map(|e| e.to_string()),
+        Err(_) if has_extension(&path, &["heic", "heif"]) => Some("heif".to_string()),
+        Err(_) => None,
+    };
+    let frame_count = animation_frames(&path)?.map(|f| f.take_while(Result::is_ok).count());
+    let error = validate_image(&path).err().map(|e| e.to_string());
+    Ok(ImageInfo {
+        width,
+        height,
+        format,
+        file_size,
+        is_animated: frame_count.is_some_and(|n| n > 1),
+        frame_count,
+        is_valid: error.is_none(),
+        error,
+    })
+}
+
+/// Load image from path and return as data URL (for preview/crop so webview doesn't need asset protocol).
+#[tauri::command]
+pub fn get_image_data_url(payload: GetImageDataUrlPayload) -> Result<String, CommandError> {
+    let path = PathBuf::from(&payload.path);
+    if !path.exists() || !path.is_file() {
+        return Err(CommandError::NotFound("File not found".to_string()));
+    }
+
+    let format = PreviewFormat::parse(payload.format.as_deref())?;
+    let filter = parse_filter(payload.filter.as_deref(), FilterType::Lanczos3)?;
+    let img = open_image(&path)?;
+    let img = fit_longest_side_with(img, payload.max_side.unwrap_or(0), filter);
+
+    let buf = format.encode(&img)?;
+    Ok(format.data_url(&buf))
+}
+
+/// Crop (and optionally flip/rotate) an image. Overwrites the file unless save_as_new is true,
+/// in which case the result carries the new path.
+#[tauri::command]
+pub fn crop_image(payload: CropImagePayload) -> Result<CropImageResult, CommandError> {
+    let path = PathBuf::from(&payload.image_path);
+    if !path.exists() || !path.is_file() {
+        return Err(CommandError::NotFound("Image file not found".to_string()));
+    }
+
+    let (img, orientation) = open_oriented(&path)?;
+
+    let (w, h) = (img.width(), img.height());
+    let display = match (payload.display_width, payload.display_height) {
+        // Rotate180 and the flips keep the size, so the orientation decides, not the dimensions.
+        (Some(dw), Some(dh)) if orientation != Orientation::NoTransforms => Some((dw, dh)),
+        _ => None,
+    };
+
+    let mut requested = (payload.x, payload.y, payload.width, payload.height);
+    if let Some(ratio) = payload.lock_aspect {
+        requested = snap_to_aspect(requested, ratio, display.unwrap_or((w, h)))?;
+    }
+    let (x, y, cw, ch) = if let Some(display) = display {
+        remap_oriented_rect(requested, display, (w, h), orientation)?
+    } else {
+        let (x, y, rw, rh) = requested;
+        let x = x.min(w.saturating_sub(1));
+        let y = y.min(h.saturating_sub(1));
+        (
+            x,
+            y,
+            rw.min(w.saturating_sub(x)),
+            rh.min(h.saturating_sub(y)),
+        )
+    };
+
+    if cw == 0 || ch == 0 {
+        return Err(CommandError::invalid("Crop region has zero size"));
+    }
+    let (bx, by, bw, bh) = if display.is_some() {
+        requested
+    } else {
+        (x, y, cw, ch)
+    };
+    let bounds = CropBounds {
+        x: bx,
+        y: by,
+        width: bw,
+        height: bh,
+    };
+
+    // Crop first (in original image coordinates), then apply flip/rotate to the cropped result
+    let mut cropped_sub = img.crop_imm(x, y, cw, ch);
+    if display.is_some() {
+        // Re-encoding drops EXIF, so the saved crop must be upright like the preview was.
+        cropped_sub.apply_orientation(orientation);
+    }
+    let mut out_img = image::DynamicImage::from(cropped_sub.to_rgb8());
+
+    if payload.flip_x {
+        out_img = out_img.fliph();
+    }
+    if payload.flip_y {
+        out_img = out_img.flipv();
+    }
+
+    let rot = ((payload.rotate_degrees % 360 + 360) % 360) / 90;
+    for _ in 0..rot {
+        out_img = out_img.rotate90();
+    }
+
+    // Optional: resize to training size (square) for LoRA
+    if let Some(sz) = payload.output_size.filter(|s| (64..=2048).contains(s)) {
+        out_img = out_img.resize(sz, sz, FilterType::Triangle);
+    }
+
+    let writable = writable_format(&path);
+    let format = writable.unwrap_or(ImageFormat::Png);
+    let (out_path, file) = if payload.save_as_new {
+        let base = if writable.is_some() {
+            path.clone()
+        } else {
+            path.with_extension("png")
+        };
+        create_unique_sibling(&base, "crop")?
+    } else {
+        if writable.is_none() {
+            return Err(CommandError::invalid(
+                "This format can't be written in place; crop with save_as_new to get a PNG",
+            ));
+        }
+        if payload.auto_backup {
+            backup_original(&path)?;
+        }
+        (path.clone(), fs::File::create(&path)?)
+    };
+
+    if let Err(e) = out_img.write_to(&mut std::io::BufWriter::new(file), format) {
+        if payload.save_as_new {
+            let _ = fs::remove_file(&out_path);
+        }
+        return Err(e.into());
+    }
+
+    if !payload.save_as_new {
+        return Ok(CropImageResult {
+            new_path: None,
+            caption_copied: false,
+            bounds,
+            width: out_img.width(),
+            height: out_img.height(),
+        });
+    }
+
+    // Copy the source caption to the new image so LoRA workflow keeps tags
+    let caption_copied = payload.copy_caption && copy_caption(&path, &out_path);
+    Ok(CropImageResult {
+        new_path: Some(out_path.to_string_lossy().into_owned()),
+        caption_copied,
+        bounds,
+        width: out_img.width(),
+        height: out_img.height(),
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RestoreOriginalPayload {
+    pub image_path: String,
+}
+
+/// Copy the `.bak` made by an in-place crop with auto_backup back over the image.
+/// The backup is kept so the restore can be repeated.
+#[tauri::command]
+pub fn restore_original(payload: RestoreOriginalPayload) -> Result<(), CommandError> {
+    let path = PathBuf::from(&payload.image_path);
+    let backup = backup_path(&path);
+    if !backup.is_file() {
+        return Err(CommandError::NotFound(
+            "No backup found for this image".to_string(),
+        ));
+    }
+    fs::copy(&backup, &path)?;
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SquareMode {
+    /// Crop to the shorter edge, centered.
+    CenterCrop,
+    /// Letterbox to the longer edge with the fill color.
+    Pad,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MakeSquarePayload {
+    pub image_path: String,
+    pub mode: SquareMode,
+    /// RGBA padding color for Pad mode (default transparent; black in formats without alpha).
+    #[serde(default)]
+    pub fill: [u8; 4],
+    #[serde(default)]
+    pub save_as_new: bool,
+    /// With save_as_new, copy the source caption to the new image (default true).
+    #[serde(default = "default_true")]
+    pub copy_caption: bool,
+}
+
+/// Make an image square by center-cropping or padding. Alpha is preserved where the format allows.
+/// Returns Some(new_path) when save_as_new is true, None otherwise (like crop_image).
+#[tauri::command]
+pub fn make_square(payload: MakeSquarePayload) -> Result<Option<String>, CommandError> {
+    let path = PathBuf::from(&payload.image_path);
+    if !path.exists() || !path.is_file() {
+        return Err(CommandError::NotFound("Image file not found".to_string()));
+    }
+
+    let img = open_image(&path)?;
+    let (w, h) = (img.width(), img.height());
+    let out_img = match payload.mode {
+        SquareMode::CenterCrop => {
+            let side = w.min(h);
+            img.crop_imm((w - side) / 2, (h - side) / 2, side, side)
+        }
+        SquareMode::Pad => {
+            let side = w.max(h);
+            let mut canvas = image::RgbaImage::from_pixel(side, side, image::Rgba(payload.fill));
+            let (x, y) = ((side - w) / 2, (side - h) / 2);
+            image::imageops::overlay(&mut canvas, &img.to_rgba8(), x as i64, y as i64);
+            image::DynamicImage::from(canvas)
+        }
+    };
+
+    save_edited(
+        &out_img,
+        &path,
+        "square",
+        payload.save_as_new,
+        payload.copy_caption,
+    )
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AutocropBordersPayload {
+    pub image_path: String,
+    /// Max per-channel difference from the border color still counted as border (default 10).
+    #[serde(default = "default_border_tolerance")]
+    pub tolerance: u8,
+    #[serde(default)]
+    pub save_as_new: bool,
+    /// With save_as_new, copy the source caption to the new image (default true).
+    #[serde(default = "default_true")]
+    pub copy_caption: bool,
+}
+
+fn default_border_tolerance() -> u8 {
+    10
+}
+
+#[derive(Debug, Serialize)]
+pub struct AutocropResult {
+    /// Content bounds in the original image.
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// False when no border was found; the image is left untouched.
+    pub cropped: bool,
+    pub new_path: Option<String>,
+}
+
+/// Trim uniform borders (letterboxing, screenshot margins) from each edge. A row or column is
+/// border while every pixel is within `tolerance` of that edge's corner color.
+#[tauri::command]
+pub fn autocrop_borders(payload: AutocropBordersPayload) -> Result<AutocropResult, CommandError> {
+    let path = PathBuf::from(&payload.image_path);
+    if !path.exists() || !path.is_file() {
+        return Err(CommandError::NotFound("Image file not found".to_string()));
+    }
+
+    let img = open_image(&path)?;
+    let rgba = img.to_rgba8();
+    let (w, h) = rgba.dimensions();
+    if w == 0 || h == 0 {
+        return Err(CommandError::invalid("Image has no pixels"));
+    }
+    let tol = payload.tolerance;
+    let near = |a: &image::Rgba<u8>, b: &image::Rgba<u8>| {
+        a.0.iter()
+            .zip(b.0.iter())
+            .all(|(x, y)| x.abs_diff(*y) <= tol)
+    };
+    let row_is = |y: u32, c: &image::Rgba<u8>, x0: u32, x1: u32| {
+        (x0..x1).all(|x| near(rgba.get_pixel(x, y), c))
+    };
+    let col_is = |x: u32, c: &image::Rgba<u8>, y0: u32, y1: u32| {
+        (y0..y1).all(|y| near(rgba.get_pixel(x, y), c))
+    };
+
+    let top_color = *rgba.get_pixel(0, 0);
+    let bottom_color = *rgba.get_pixel(0, h - 1);
+    let mut top = 0;
+    while top < h && row_is(top, &top_color, 0, w) {
+        top += 1;
+    }
+    if top == h {
+        return Err(CommandError::invalid("Image is a single uniform color"));
+    }
+    let mut bottom = h;
+    while bottom > top && row_is(bottom - 1, &bottom_color, 0, w) {
+        bottom -= 1;
+    }
+    let left_color = *rgba.get_pixel(0, top);
+    let right_color = *rgba.get_pixel(w - 1, top);
+    let mut left = 0;
+    while left < w && col_is(left, &left_color, top, bottom) {
+        left += 1;
+    }
+    let mut right = w;
+    while right > left && col_is(right - 1, &right_color, top, bottom) {
+        right -= 1;
+    }
+
+    let (cw, ch) = (right - left, bottom - top);
+    if (cw, ch) == (w, h) {
+        return Ok(AutocropResult {
+            x: 0,
+            y: 0,
+            width: w,
+            height: h,
+            cropped: false,
+            new_path: None,
+        });
+    }
+    let out_img = img.crop_imm(left, top, cw, ch);
+    let new_path = save_edited(
+        &out_img,
+        &path,
+        "autocrop",
+        payload.save_as_new,
+        payload.copy_caption,
+    )?;
+    Ok(AutocropResult {
+        x: left,
+        y: top,
+        width: cw,
+        height: ch,
+        cropped: true,
+        new_path,
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RotateImagePayload {
+    pub image_path: String,
+    /// Clockwise rotation in degrees (any angle).
+    pub degrees: f32,
+    /// Grow the canvas to fit the rotated image; otherwise keep the original size (corners clipped).
+    #[serde(default)]
+    pub expand: bool,
+    /// RGBA color for uncovered corners (default transparent; black in formats without alpha).
+    #[serde(default)]
+    pub fill: [u8; 4],
+    #[serde(default)]
+    pub save_as_new: bool,
+    /// With save_as_new, copy the source caption to the new image (default true).
+    #[serde(default = "default_true")]
+    pub copy_caption: bool,
+}
+
+/// Rotate by an arbitrary angle around the center with bilinear sampling.
+fn rotate_arbitrary(
+    img: &image::DynamicImage,
+    degrees: f32,
+    expand: bool,
+    fill: [u8; 4],
+) -> image::RgbaImage {
+    let src = img.to_rgba8();
+    let (w, h) = (src.width() as f32, src.height() as f32);
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let (out_w, out_h) = if expand {
+        (
+            (w * cos.abs() + h * sin.abs()).round().max(1.0) as u32,
+            (w * sin.abs() + h * cos.abs()).round().max(1.0) as u32,
+        )
+    } else {
+        (src.width(), src.height())
+    };
+    let (src_cx, src_cy) = (w / 2.0, h / 2.0);
+    let (out_cx, out_cy) = (out_w as f32 / 2.0, out_h as f32 / 2.0);
+
+    let pixel_at = |x: i64, y: i64| -> [f32; 4] {
+        if x < 0 || y < 0 || x >= src.width() as i64 || y >= src.height() as i64 {
+            fill.map(f32::from)
+        } else {
+            src.get_pixel(x as u32, y as u32).0.map(f32::from)
+        }
+    };
+
+    let mut raw = vec![0u8; out_w as usize * out_h as usize * 4];
+    raw.par_chunks_mut(out_w as usize * 4)
+        .enumerate()
+        .for_each(|(oy, row)| {
+            for ox in 0..out_w as usize {
+                // Inverse-rotate the output pixel center back into source coordinates.
+                let dx = ox as f32 + 0.5 - out_cx;
+                let dy = oy as f32 + 0.5 - out_cy;
+                let sx = dx * cos + dy * sin + src_cx - 0.5;
+                let sy = -dx * sin + dy * cos + src_cy - 0.5;
+                let (x0, y0) = (sx.floor(), sy.floor());
+                let (fx, fy) = (sx - x0, sy - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+                let p00 = pixel_at(x0, y0);
+                let p10 = pixel_at(x0 + 1, y0);
+                let p01 = pixel_at(x0, y0 + 1);
+                let p11 = pixel_at(x0 + 1, y0 + 1);
+                for c in 0..4 {
+                    let top = p00[c] + (p10[c] - p00[c]) * fx;
+                    let bottom = p01[c] + (p11[c] - p01[c]) * fx;
+                    row[ox * 4 + c] = (top + (bottom - top) * fy).round().clamp(0.0, 255.0) as u8;
+                }
+            }
+        });
+    image::RgbaImage::from_raw(out_w, out_h, raw).expect("buffer matches dimensions")
+}
+
+/// Rotate an image by any angle (e.g. to straighten a tilted photo). Complements the 90° steps in
+/// crop_image. Returns Some(new_path) when save_as_new is true, None otherwise.
+#[tauri::command]
+pub fn rotate_image(payload: RotateImagePayload) -> Result<Option<String>, CommandError> {
+    let path = PathBuf::from(&payload.image_path);
+    if !path.exists() || !path.is_file() {
+        return Err(CommandError::NotFound("Image file not found".to_string()));
+    }
+    if !payload.degrees.is_finite() {
+        return Err(CommandError::invalid(
+            "Rotation angle must be a finite number",
+        ));
+    }
+
+    let img = open_image(&path)?;
+    let rotated = rotate_arbitrary(&img, payload.degrees, payload.expand, payload.fill);
+
+    save_edited(
+        &image::DynamicImage::from(rotated),
+        &path,
+        "rotated",
+        payload.save_as_new,
+        payload.copy_caption,
+    )
+}
+
 #[derive(Debug, Deserialize)]
-pub struct CropImagePayload {
+pub struct FlipImagePayload {
     pub image_path: String,
-    pub x: u32,
-    pub y: u32,
-    pub width: u32,
-    pub height: u32,
-    #[serde(default)]
-    pub flip_x: bool,
     #[serde(default)]
-    pub flip_y: bool,
+    pub horizontal: bool,
     #[serde(default)]
-    pub rotate_degrees: i32,
-    /// If true, save cropped image to a new file (keeps original). Returns new path.
+    pub vertical: bool,
     #[serde(default)]
     pub save_as_new: bool,
-    /// If set, resize output to this size (square) for LoRA/training (e.g. 512 or 1024).
-    #[serde(default)]
-    pub output_size: Option<u32>,
+    /// With save_as_new, copy the source caption to the new image (default true).
+    #[serde(default = "default_true")]
+    pub copy_caption: bool,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct GetThumbnailPayload {
-    pub path: String,
-    #[serde(default)]
-    pub size: Option<u32>,
+/// Mirror a whole image without a crop region. Returns Some(new_path) when save_as_new is true.
+#[tauri::command]
+pub fn flip_image(payload: FlipImagePayload) -> Result<Option<String>, CommandError> {
+    let path = PathBuf::from(&payload.image_path);
+    if !path.exists() || !path.is_file() {
+        return Err(CommandError::NotFound("Image file not found".to_string()));
+    }
+    if !payload.horizontal && !payload.vertical {
+        return Err(CommandError::invalid(
+            "Choose horizontal and/or vertical flip",
+        ));
+    }
+
+    let mut img = open_image(&path)?;
+    if payload.horizontal {
+        img = img.fliph();
+    }
+    if payload.vertical {
+        img = img.flipv();
+    }
+    save_edited(
+        &img,
+        &path,
+        "flipped",
+        payload.save_as_new,
+        payload.copy_caption,
+    )
 }
 
 #[derive(Debug, Deserialize)]
-pub struct GetImageDataUrlPayload {
-    pub path: String,
-    /// Max length of the longest side (for preview); 0 = full size.
+pub struct RotateImage90Payload {
+    pub image_path: String,
+    /// Quarter turns clockwise; negative turns rotate counter-clockwise.
+    pub turns: i32,
     #[serde(default)]
-    pub max_side: Option<u32>,
+    pub save_as_new: bool,
+    /// With save_as_new, copy the source caption to the new image (default true).
+    #[serde(default = "default_true")]
+    pub copy_caption: bool,
 }
 
-/// Generates a thumbnail for the image at path. Returns a data URL (base64 JPEG).
-/// Uses an on-disk cache under temp (keyed by path + mtime + size) to avoid regenerating on scroll.
+/// Rotate a whole image in 90° steps (lossless in pixels, unlike rotate_image). Returns
+/// Some(new_path) when save_as_new is true.
 #[tauri::command]
-pub fn get_thumbnail(payload: GetThumbnailPayload) -> Result<String, String> {
-    let path = PathBuf::from(&payload.path);
+pub fn rotate_image_90(payload: RotateImage90Payload) -> Result<Option<String>, CommandError> {
+    let path = PathBuf::from(&payload.image_path);
     if !path.exists() || !path.is_file() {
-        return Err("File not found".to_string());
+        return Err(CommandError::NotFound("Image file not found".to_string()));
     }
+    let img = open_image(&path)?;
+    let rotated = match payload.turns.rem_euclid(4) {
+        1 => img.rotate90(),
+        2 => img.rotate180(),
+        3 => img.rotate270(),
+        _ => {
+            return Err(CommandError::invalid(
+                "Rotation must not be a multiple of four turns",
+            ))
+        }
+    };
+    save_edited(
+        &rotated,
+        &path,
+        "rotated",
+        payload.save_as_new,
+        payload.copy_caption,
+    )
+}
 
-    let size = payload.size.unwrap_or(THUMB_SIZE).min(512);
-    let cache_dir = thumbnail_cache_dir()?;
-    let key = thumbnail_cache_key(&path, size)?;
-    let cache_path = cache_dir.join(format!("{}.jpg", key));
-
-    if cache_path.exists() && cache_path.is_file() {
-        let mut buf = Vec::new();
-        let mut f = fs::File::open(&cache_path).map_err(|e| e.to_string())?;
-        f.read_to_end(&mut buf).map_err(|e| e.to_string())?;
-        let b64 = BASE64.encode(&buf);
-        return Ok(format!("data:image/jpeg;base64,{b64}"));
-    }
+#[derive(Debug, Deserialize)]
+pub struct AdjustImagePayload {
+    pub image_path: String,
+    /// Added to each channel (-255..255); 0 = unchanged.
+    #[serde(default)]
+    pub brightness: i32,
+    /// Multiplier around mid-gray; 1.0 = unchanged.
+    #[serde(default = "default_one")]
+    pub contrast: f32,
+    /// HSV saturation multiplier; 0.0 = grayscale, 1.0 = unchanged.
+    #[serde(default = "default_one")]
+    pub saturation: f32,
+    #[serde(default)]
+    pub save_as_new: bool,
+    /// With save_as_new, copy the source caption to the new image (default true).
+    #[serde(default = "default_true")]
+    pub copy_caption: bool,
+}
 
-    let img = image::open(&path).map_err(|e| e.to_string())?;
-    let thumb = img.resize(size, size, FilterType::Triangle);
-    let mut buf = Vec::new();
-    thumb
-        .write_to(&mut Cursor::new(&mut buf), ImageFormat::Jpeg)
-        .map_err(|e| e.to_string())?;
+fn default_one() -> f32 {
+    1.0
+}
 
-    if let Ok(mut f) = fs::File::create(&cache_path) {
-        let _ = f.write_all(&buf);
-    }
+fn rgb_to_hsv(r: f32, g: f32, b: f32) -> (f32, f32, f32) {
+    let max = r.max(g).max(b);
+    let delta = max - r.min(g).min(b);
+    let h = if delta == 0.0 {
+        0.0
+    } else if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    let s = if max == 0.0 { 0.0 } else { delta / max };
+    (h, s, max)
+}
 
-    let b64 = BASE64.encode(&buf);
-    Ok(format!("data:image/jpeg;base64,{b64}"))
+fn hsv_to_rgb(h: f32, s: f32, v: f32) -> (f32, f32, f32) {
+    let c = v * s;
+    let x = c * (1.0 - ((h / 60.0).rem_euclid(2.0) - 1.0).abs());
+    let m = v - c;
+    let (r, g, b) = match (h / 60.0) as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    (r + m, g + m, b + m)
 }
 
-/// Load image from path and return as data URL (for preview/crop so webview doesn't need asset protocol).
-#[tauri::command]
-pub fn get_image_data_url(payload: GetImageDataUrlPayload) -> Result<String, String> {
-    let path = PathBuf::from(&payload.path);
-    if !path.exists() || !path.is_file() {
-        return Err("File not found".to_string());
+/// Apply brightness, then contrast, then saturation. Neutral values skip their pass entirely.
+fn adjust_tones(
+    img: image::DynamicImage,
+    brightness: i32,
+    contrast: f32,
+    saturation: f32,
+) -> image::DynamicImage {
+    let mut img = if brightness != 0 {
+        img.brighten(brightness.clamp(-255, 255))
+    } else {
+        img
+    };
+    if contrast == 1.0 && saturation == 1.0 {
+        return img;
     }
 
-    let mut img = image::open(&path).map_err(|e| e.to_string())?;
-    let max_side = payload.max_side.unwrap_or(0);
-    if max_side > 0 {
-        let (w, h) = (img.width(), img.height());
-        let longest = w.max(h);
-        if longest > max_side {
-            let scale = max_side as f32 / longest as f32;
-            let new_w = (w as f32 * scale).round() as u32;
-            let new_h = (h as f32 * scale).round() as u32;
-            img = img.resize(new_w, new_h, FilterType::Triangle);
+    let had_alpha = img.color().has_alpha();
+    let mut rgba = img.to_rgba8();
+    rgba.par_chunks_mut(4).for_each(|px| {
+        let mut rgb = [px[0], px[1], px[2]].map(|c| c as f32 / 255.0);
+        if contrast != 1.0 {
+            rgb = rgb.map(|c| (c - 0.5) * contrast + 0.5);
+        }
+        if saturation != 1.0 {
+            let [r, g, b] = rgb.map(|c| c.clamp(0.0, 1.0));
+            let (h, s, v) = rgb_to_hsv(r, g, b);
+            let (r, g, b) = hsv_to_rgb(h, (s * saturation).clamp(0.0, 1.0), v);
+            rgb = [r, g, b];
+        }
+        for (dst, c) in px.iter_mut().zip(rgb) {
+            *dst = (c * 255.0).round().clamp(0.0, 255.0) as u8;
         }
+    });
+    img = image::DynamicImage::from(rgba);
+    if had_alpha {
+        img
+    } else {
+        image::DynamicImage::from(img.to_rgb8())
     }
-
-    let mut buf = Vec::new();
-    img.write_to(&mut Cursor::new(&mut buf), ImageFormat::Jpeg)
-        .map_err(|e| e.to_string())?;
-    let b64 = BASE64.encode(&buf);
-    Ok(format!("data:image/jpeg;base64,{b64}"))
 }
 
-/// Crop (and optionally flip/rotate) an image. Overwrites the file unless save_as_new is true.
+/// Basic tone adjustments for normalizing mixed-exposure datasets.
 /// Returns Some(new_path) when save_as_new is true, None otherwise.
 #[tauri::command]
-pub fn crop_image(payload: CropImagePayload) -> Result<Option<String>, String> {
+pub fn adjust_image(payload: AdjustImagePayload) -> Result<Option<String>, CommandError> {
     let path = PathBuf::from(&payload.image_path);
     if !path.exists() || !path.is_file() {
-        return Err("Image file not found".to_string());
+        return Err(CommandError::NotFound("Image file not found".to_string()));
+    }
+    if !payload.contrast.is_finite() || !payload.saturation.is_finite() {
+        return Err(CommandError::invalid(
+            "Contrast and saturation must be finite numbers",
+        ));
     }
 
-    let img = image::open(&path).map_err(|e| e.to_string())?;
+    let img = open_image(&path)?;
+    let out_img = adjust_tones(
+        img,
+        payload.brightness,
+        payload.contrast.max(0.0),
+        payload.saturation.max(0.0),
+    );
 
-    let (w, h) = (img.width(), img.height());
-    let x = payload.x.min(w.saturating_sub(1));
-    let y = payload.y.min(h.saturating_sub(1));
-    let cw = payload.width.min(w.saturating_sub(x));
-    let ch = payload.height.min(h.saturating_sub(y));
+    save_edited(
+        &out_img,
+        &path,
+        "adjusted",
+        payload.save_as_new,
+        payload.copy_caption,
+    )
+}
 
-    if cw == 0 || ch == 0 {
-        return Err("Crop region has zero size".to_string());
-    }
+/// Images are downscaled to this longest side before histogramming.
+const HISTOGRAM_MAX_SIDE: u32 = 512;
 
-    // Crop first (in original image coordinates), then apply flip/rotate to the cropped result
-    let cropped_sub = img.crop_imm(x, y, cw, ch);
-    let mut out_img = image::DynamicImage::from(cropped_sub.to_rgb8());
+#[derive(Debug, Deserialize)]
+pub struct ImageHistogramPayload {
+    pub image_path: String,
+    /// Number of bins per channel, 2..=256 (default 256).
+    #[serde(default)]
+    pub buckets: Option<usize>,
+}
 
-    if payload.flip_x {
-        out_img = out_img.fliph();
-    }
-    if payload.flip_y {
-        out_img = out_img.flipv();
-    }
+#[derive(Debug, Serialize)]
+pub struct ImageHistogram {
+    pub red: Vec<u32>,
+    pub green: Vec<u32>,
+    pub blue: Vec<u32>,
+    pub luma: Vec<u32>,
+    /// Luma statistics, 0..=255.
+    pub mean: f32,
+    pub min: u8,
+    pub max: u8,
+    /// Fraction of pixels whose luma is pure black or pure white.
+    pub clipping_ratio: f32,
+}
 
-    let rot = ((payload.rotate_degrees % 360 + 360) % 360) / 90;
-    for _ in 0..rot {
-        out_img = out_img.rotate90();
+/// Per-channel and luma histograms with exposure stats, for flagging blown-out or crushed images.
+#[tauri::command]
+pub fn get_image_histogram(payload: ImageHistogramPayload) -> Result<ImageHistogram, CommandError> {
+    let path = PathBuf::from(&payload.image_path);
+    if !path.is_file() {
+        return Err(CommandError::NotFound("Image file not found".to_string()));
     }
-
-    // Optional: resize to training size (square) for LoRA
-    if let Some(sz) = payload.output_size.filter(|&s| s >= 64 && s <= 2048) {
-        out_img = out_img.resize(sz, sz, FilterType::Triangle);
+    let buckets = payload.buckets.unwrap_or(256).clamp(2, 256);
+    let img = fit_longest_side(open_image(&path)?, HISTOGRAM_MAX_SIDE);
+    let rgb = img.to_rgb8();
+    let pixels = rgb.width() as u64 * rgb.height() as u64;
+    if pixels == 0 {
+        return Err(CommandError::invalid("Image has no pixels"));
     }
 
-    let format = ImageFormat::from_path(&path).unwrap_or(ImageFormat::Png);
-    let ext = path
-        .extension()
-        .and_then(|e| e.to_str())
-        .unwrap_or("png");
-    let out_path: PathBuf = if payload.save_as_new {
-        let parent = path.parent().unwrap_or_else(|| path.as_path());
-        let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("image");
-        let mut n = 1u32;
-        loop {
-            let name = format!("{}_{}_crop.{}", stem, n, ext);
-            let candidate = parent.join(&name);
-            if !candidate.exists() {
-                break candidate;
-            }
-            n += 1;
-            if n > 9999 {
-                return Err("Could not create unique filename for new image".to_string());
-            }
-        }
-    } else {
-        path.clone()
+    let bin = |v: u8| v as usize * buckets / 256;
+    let mut hist = ImageHistogram {
+        red: vec![0; buckets],
+        green: vec![0; buckets],
+        blue: vec![0; buckets],
+        luma: vec![0; buckets],
+        mean: 0.0,
+        min: u8::MAX,
+        max: 0,
+        clipping_ratio: 0.0,
     };
+    let mut sum = 0u64;
+    let mut clipped = 0u64;
+    for p in rgb.pixels() {
+        let [r, g, b] = p.0;
+        // Rec. 601 luma, as used by image's grayscale conversion.
+        let l = ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114 + 500) / 1000) as u8;
+        hist.red[bin(r)] += 1;
+        hist.green[bin(g)] += 1;
+        hist.blue[bin(b)] += 1;
+        hist.luma[bin(l)] += 1;
+        sum += l as u64;
+        hist.min = hist.min.min(l);
+        hist.max = hist.max.max(l);
+        if l == 0 || l == 255 {
+            clipped += 1;
+        }
+    }
+    hist.mean = sum as f32 / pixels as f32;
+    hist.clipping_ratio = clipped as f32 / pixels as f32;
+    Ok(hist)
+}
 
-    let mut file = std::io::BufWriter::new(
-        std::fs::File::create(&out_path).map_err(|e| e.to_string())?,
-    );
-    out_img
-        .write_to(&mut file, format)
-        .map_err(|e| e.to_string())?;
-
-    // When saving as new, copy the source caption to the new image so LoRA workflow keeps tags
-    if payload.save_as_new {
-        let caption_path = path.with_extension("txt");
-        if caption_path.exists() {
-            if let Ok(content) = fs::read_to_string(&caption_path) {
-                let out_txt = out_path.with_extension("txt");
-                let _ = fs::write(out_txt, content.trim());
-            }
+/// Images are downscaled to this longest side before measuring sharpness, so scores are
+/// comparable across source resolutions.
+const SHARPNESS_MAX_SIDE: u32 = 512;
+/// Heuristic cut-off for `likely_blurry`. Depends on content and on SHARPNESS_MAX_SIDE; tune it
+/// per dataset (flat illustrations score low without being blurry).
+pub const DEFAULT_BLUR_THRESHOLD: f64 = 100.0;
+
+/// Variance of the 4-neighbour Laplacian of the grayscale image. Higher is sharper.
+fn laplacian_variance(img: &image::DynamicImage) -> f64 {
+    let gray = img.to_luma8();
+    let (w, h) = gray.dimensions();
+    if w < 3 || h < 3 {
+        return 0.0;
+    }
+    let px = |x: u32, y: u32| gray.get_pixel(x, y)[0] as f64;
+    let (mut sum, mut sum_sq) = (0.0, 0.0);
+    for y in 1..h - 1 {
+        for x in 1..w - 1 {
+            let lap = px(x - 1, y) + px(x + 1, y) + px(x, y - 1) + px(x, y + 1) - 4.0 * px(x, y);
+            sum += lap;
+            sum_sq += lap * lap;
         }
     }
+    let n = ((w - 2) * (h - 2)) as f64;
+    let mean = sum / n;
+    sum_sq / n - mean * mean
+}
 
-    Ok(if payload.save_as_new {
-        Some(out_path.to_string_lossy().into_owned())
-    } else {
-        None
+/// Sharpness score of an image file (see laplacian_variance), after downscaling.
+pub fn sharpness_score(path: &Path) -> Result<f64, CommandError> {
+    let img = open_image(path)?;
+    Ok(laplacian_variance(&fit_longest_side(
+        img,
+        SHARPNESS_MAX_SIDE,
+    )))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AssessSharpnessBatchPayload {
+    pub paths: Vec<String>,
+    /// Scores below this are flagged likely_blurry (default DEFAULT_BLUR_THRESHOLD).
+    #[serde(default)]
+    pub threshold: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SharpnessResult {
+    pub path: String,
+    /// None when the image couldn't be decoded.
+    pub sharpness: Option<f64>,
+    pub likely_blurry: bool,
+    pub error: Option<String>,
+}
+
+/// Blur detection for a batch of images, in parallel. The threshold is a heuristic: scores depend
+/// on content as well as focus, so treat likely_blurry as a triage hint.
+#[tauri::command]
+pub async fn assess_sharpness_batch(
+    payload: AssessSharpnessBatchPayload,
+) -> Result<Vec<SharpnessResult>, CommandError> {
+    let threshold = payload.threshold.unwrap_or(DEFAULT_BLUR_THRESHOLD);
+    tauri::async_runtime::spawn_blocking(move || {
+        payload
+            .paths
+            .par_iter()
+            .map(|path| match sharpness_score(Path::new(path)) {
+                Ok(score) => SharpnessResult {
+                    path: path.clone(),
+                    sharpness: Some(score),
+                    likely_blurry: score < threshold,
+                    error: None,
+                },
+                Err(e) => SharpnessResult {
+                    path: path.clone(),
+                    sharpness: None,
+                    likely_blurry: false,
+                    error: Some(e.to_string()),
+                },
+            })
+            .collect()
     })
+    .await
+    .map_err(CommandError::from)
 }
 
 #[derive(Debug, serde::Deserialize)]
@@ -257,14 +1584,16 @@ pub struct BatchResizeResult {
 
 /// Batch resize/preprocess images to target size. Outputs to specified folder, copies captions.
 #[tauri::command]
-pub fn batch_resize(payload: BatchResizePayload) -> Result<BatchResizeResult, String> {
+pub fn batch_resize(payload: BatchResizePayload) -> Result<BatchResizeResult, CommandError> {
     if payload.target_size < 64 || payload.target_size > 2048 {
-        return Err("Target size must be between 64 and 2048".to_string());
+        return Err(CommandError::invalid(
+            "Target size must be between 64 and 2048",
+        ));
     }
     let target = payload.target_size;
 
     let out_dir = PathBuf::from(&payload.output_folder);
-    fs::create_dir_all(&out_dir).map_err(|e| e.to_string())?;
+    fs::create_dir_all(&out_dir)?;
 
     let mut processed = 0usize;
     let mut skipped = 0usize;
@@ -277,14 +1606,9 @@ pub fn batch_resize(payload: BatchResizePayload) -> Result<BatchResizeResult, St
             continue;
         }
 
-        let ext = path
-            .extension()
-            .and_then(|e| e.to_str())
-            .unwrap_or("png");
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("png");
         let new_name = format!("{:04}.{}", i + 1, ext);
         let out_img = out_dir.join(&new_name);
-        let base = new_name.rsplit_once('.').map(|n| n.0).unwrap_or(&new_name);
-        let out_txt = out_dir.join(format!("{}.txt", base));
 
         let img = match image::open(&path) {
             Ok(i) => i,
@@ -320,19 +1644,14 @@ pub fn batch_resize(payload: BatchResizePayload) -> Result<BatchResizeResult, St
         };
 
         let format = ImageFormat::from_path(&path).unwrap_or(ImageFormat::Png);
-        let mut out_file = fs::File::create(&out_img).map_err(|e| e.to_string())?;
+        let mut out_file = fs::File::create(&out_img)?;
         if out_img_dyn.write_to(&mut out_file, format).is_err() {
             skipped += 1;
             continue;
         }
 
         // Copy caption if exists
-        let caption_path = path.with_extension("txt");
-        if caption_path.exists() {
-            if let Ok(content) = fs::read_to_string(&caption_path) {
-                let _ = fs::write(&out_txt, content.trim());
-            }
-        }
+        copy_caption(&path, &out_img);
 
         output_paths.push(out_img.to_string_lossy().into_owned());
         processed += 1;
@@ -346,21 +1665,290 @@ pub fn batch_resize(payload: BatchResizePayload) -> Result<BatchResizeResult, St
     })
 }
 
-/// Delete an image file and its caption .txt from disk.
+/// Delete an image file and its caption sidecars from disk.
 #[tauri::command]
-pub fn delete_image(image_path: String) -> Result<(), String> {
+pub fn delete_image(image_path: String) -> Result<(), CommandError> {
     let path = PathBuf::from(&image_path);
     if !path.exists() || !path.is_file() {
-        return Err("Image file not found".to_string());
+        return Err(CommandError::NotFound("Image file not found".to_string()));
+    }
+    std::fs::remove_file(&path)?;
+    for ext in sidecar::DETECT_ORDER {
+        let caption_path = path.with_extension(ext.extension());
+        if caption_path.is_file() {
+            let _ = std::fs::remove_file(&caption_path);
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RelocateImagePayload {
+    pub src: String,
+    pub dest_dir: String,
+    /// Replace an image with the same name (and its captions) in dest_dir. Otherwise the moved or
+    /// copied image gets a numeric suffix (`name_1.png`).
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+/// First `{stem}_{n}.{ext}` in the folder where neither the image nor any caption sidecar exists.
+fn free_image_path(path: &Path) -> Result<PathBuf, CommandError> {
+    let taken = |p: &Path| p.exists() || !sidecar::existing_all(p).is_empty();
+    if !taken(path) {
+        return Ok(path.to_path_buf());
+    }
+    let parent = path.parent().unwrap_or(path);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("image");
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("png");
+    for n in 1..=9999u32 {
+        let candidate = parent.join(format!("{}_{}.{}", stem, n, ext));
+        if !taken(&candidate) {
+            return Ok(candidate);
+        }
+    }
+    Err(CommandError::Io(
+        "Could not find a free filename in the destination folder".to_string(),
+    ))
+}
+
+/// Rename, falling back to copy + delete when that fails (e.g. across filesystems).
+fn move_file(src: &Path, dest: &Path) -> std::io::Result<()> {
+    if fs::rename(src, dest).is_ok() {
+        return Ok(());
+    }
+    fs::copy(src, dest)?;
+    fs::remove_file(src)
+}
+
+/// Move or copy an image and its caption sidecars into dest_dir. Returns the new image path.
+fn relocate_image(payload: RelocateImagePayload, copy: bool) -> Result<String, CommandError> {
+    let src = PathBuf::from(&payload.src);
+    if !src.is_file() {
+        return Err(CommandError::NotFound("Image file not found".to_string()));
+    }
+    let dest_dir = PathBuf::from(&payload.dest_dir);
+    if !dest_dir.is_dir() {
+        return Err(CommandError::NotFound(
+            "Destination folder does not exist".to_string(),
+        ));
+    }
+    let Some(name) = src.file_name() else {
+        return Err(CommandError::invalid("Invalid image path"));
+    };
+    let mut dest = dest_dir.join(name);
+    let same_file = dest
+        .canonicalize()
+        .is_ok_and(|d| src.canonicalize().is_ok_and(|s| s == d));
+    if same_file && !copy {
+        return Ok(payload.src);
+    }
+    if payload.overwrite && !same_file {
+        if dest.is_file() {
+            fs::remove_file(&dest)?;
+        }
+        for caption in sidecar::existing_all(&dest) {
+            fs::remove_file(&caption)?;
+        }
+    } else {
+        dest = free_image_path(&dest)?;
+    }
+
+    let captions = sidecar::existing_all(&src);
+    if copy {
+        fs::copy(&src, &dest)?;
+    } else {
+        move_file(&src, &dest)?;
+    }
+    for caption in captions {
+        let ext = caption
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("txt");
+        let caption_dest = dest.with_extension(ext);
+        if copy {
+            fs::copy(&caption, &caption_dest)?;
+        } else {
+            move_file(&caption, &caption_dest)?;
+        }
+    }
+    Ok(dest.to_string_lossy().to_string())
+}
+
+/// Move an image and its captions into another folder. Returns the new image path.
+#[tauri::command]
+pub fn move_image(payload: RelocateImagePayload) -> Result<String, CommandError> {
+    relocate_image(payload, false)
+}
+
+/// Copy an image and its captions into another folder. Returns the new image path.
+#[tauri::command]
+pub fn copy_image(payload: RelocateImagePayload) -> Result<String, CommandError> {
+    relocate_image(payload, true)
+}
+
+/// Trashed files go to `.lora-studio/trash/<batch id>/` next to the image: same filesystem, so
+/// trashing is a rename, and hidden from project walks.
+const TRASH_DIR: &str = ".lora-studio/trash";
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteImagesBulkPayload {
+    pub paths: Vec<String>,
+    /// Delete outright instead of trashing (no undo).
+    #[serde(default)]
+    pub permanent: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeleteImageResult {
+    pub path: String,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashedItem {
+    pub original: String,
+    pub trashed: String,
+}
+
+/// Hand back to restore_trashed to undo a bulk delete.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UndoToken {
+    pub id: String,
+    pub items: Vec<TrashedItem>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeleteImagesBulkResult {
+    pub results: Vec<DeleteImageResult>,
+    /// Set when files were trashed (not for permanent deletes).
+    pub undo_token: Option<UndoToken>,
+}
+
+fn trash_batch_id(paths: &[String]) -> String {
+    let mut hasher = Sha256::new();
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    hasher.update(now.to_le_bytes());
+    for path in paths {
+        hasher.update(path.as_bytes());
+    }
+    hex::encode(&hasher.finalize()[..8])
+}
+
+/// Trash (or delete) one image and its caption sidecars, recording what was moved.
+fn delete_one(
+    image: &Path,
+    batch_id: &str,
+    permanent: bool,
+    trashed: &mut Vec<TrashedItem>,
+) -> Result<(), CommandError> {
+    if !image.is_file() {
+        return Err(CommandError::NotFound("Image file not found".to_string()));
     }
-    std::fs::remove_file(&path).map_err(|e| e.to_string())?;
-    let txt_path = path.with_extension("txt");
-    if txt_path.exists() && txt_path.is_file() {
-        let _ = std::fs::remove_file(&txt_path);
+    let mut files = vec![image.to_path_buf()];
+    files.extend(sidecar::existing_all(image));
+    for file in files {
+        if permanent {
+            fs::remove_file(&file)?;
+            continue;
+        }
+        let parent = file.parent().unwrap_or(Path::new("."));
+        let dir = parent.join(TRASH_DIR).join(batch_id);
+        fs::create_dir_all(&dir)?;
+        let name = file
+            .file_name()
+            .ok_or_else(|| CommandError::invalid("Invalid image path"))?;
+        let dest = free_image_path(&dir.join(name))?;
+        move_file(&file, &dest)?;
+        trashed.push(TrashedItem {
+            original: file.to_string_lossy().to_string(),
+            trashed: dest.to_string_lossy().to_string(),
+        });
     }
     Ok(())
 }
 
+/// Delete many images and their captions at once. By default they are moved to a trash folder
+/// and the returned undo_token restores them; `permanent` removes them for good.
+#[tauri::command]
+pub fn delete_images_bulk(
+    payload: DeleteImagesBulkPayload,
+) -> Result<DeleteImagesBulkResult, CommandError> {
+    let batch_id = trash_batch_id(&payload.paths);
+    let mut trashed = Vec::new();
+    let results = payload
+        .paths
+        .iter()
+        .map(|path| {
+            let outcome = delete_one(Path::new(path), &batch_id, payload.permanent, &mut trashed);
+            DeleteImageResult {
+                path: path.clone(),
+                success: outcome.is_ok(),
+                error: outcome.err().map(|e| e.to_string()),
+            }
+        })
+        .collect();
+    let undo_token = (!payload.permanent && !trashed.is_empty()).then_some(UndoToken {
+        id: batch_id,
+        items: trashed,
+    });
+    Ok(DeleteImagesBulkResult {
+        results,
+        undo_token,
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RestoreTrashedPayload {
+    pub undo_token: UndoToken,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RestoreTrashedResult {
+    pub restored_count: usize,
+    pub errors: Vec<String>,
+}
+
+/// Undo delete_images_bulk: move trashed files back. A file whose original path has been taken
+/// again is left in the trash and reported.
+#[tauri::command]
+pub fn restore_trashed(
+    payload: RestoreTrashedPayload,
+) -> Result<RestoreTrashedResult, CommandError> {
+    let mut restored_count = 0usize;
+    let mut errors = Vec::new();
+    for item in &payload.undo_token.items {
+        let (original, trashed) = (Path::new(&item.original), Path::new(&item.trashed));
+        let in_batch = trashed
+            .parent()
+            .is_some_and(|dir| dir.ends_with(Path::new(TRASH_DIR).join(&payload.undo_token.id)));
+        if !in_batch {
+            errors.push(format!("{}: not in this trash batch", item.trashed));
+            continue;
+        }
+        if original.exists() {
+            errors.push(format!("{}: a file already exists there", item.original));
+            continue;
+        }
+        match move_file(trashed, original) {
+            Ok(()) => restored_count += 1,
+            Err(e) => errors.push(format!("{}: {}", item.original, e)),
+        }
+        if let Some(dir) = trashed.parent() {
+            // Only succeeds once the batch folder is empty.
+            let _ = fs::remove_dir(dir);
+        }
+    }
+    Ok(RestoreTrashedResult {
+        restored_count,
+        errors,
+    })
+}
+
 #[derive(Debug, Deserialize)]
 pub struct CropRect {
     pub x: u32,
@@ -387,19 +1975,16 @@ pub struct MultiCropPayload {
 /// Crop an image multiple times with different regions, saving each with a suffix.
 /// Returns Vec of output paths.
 #[tauri::command]
-pub fn multi_crop(payload: MultiCropPayload) -> Result<Vec<String>, String> {
+pub fn multi_crop(payload: MultiCropPayload) -> Result<Vec<String>, CommandError> {
     let path = PathBuf::from(&payload.image_path);
     if !path.exists() || !path.is_file() {
-        return Err("Image file not found".to_string());
+        return Err(CommandError::NotFound("Image file not found".to_string()));
     }
 
-    let img = image::open(&path).map_err(|e| e.to_string())?;
+    let img = image::open(&path)?;
     let (img_w, img_h) = (img.width(), img.height());
     let format = ImageFormat::from_path(&path).unwrap_or(ImageFormat::Png);
-    let ext = path
-        .extension()
-        .and_then(|e| e.to_str())
-        .unwrap_or("png");
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("png");
     let parent = path.parent().unwrap_or_else(|| path.as_path());
     let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("image");
 
@@ -437,27 +2022,17 @@ pub fn multi_crop(payload: MultiCropPayload) -> Result<Vec<String>, String> {
         let out_name = format!("{}{}.{}", stem, crop.suffix, ext);
         let out_path = parent.join(&out_name);
 
-        let mut file = std::io::BufWriter::new(
-            std::fs::File::create(&out_path).map_err(|e| e.to_string())?,
-        );
-        out_img
-            .write_to(&mut file, format)
-            .map_err(|e| e.to_string())?;
+        let mut file = std::io::BufWriter::new(std::fs::File::create(&out_path)?);
+        out_img.write_to(&mut file, format)?;
 
         // Copy caption to new file with suffix
-        let caption_path = path.with_extension("txt");
-        if caption_path.exists() {
-            if let Ok(content) = fs::read_to_string(&caption_path) {
-                let out_txt = out_path.with_extension("txt");
-                let _ = fs::write(out_txt, content.trim());
-            }
-        }
+        copy_caption(&path, &out_path);
 
         output_paths.push(out_path.to_string_lossy().into_owned());
     }
 
     if output_paths.is_empty() {
-        return Err("No valid crops processed".to_string());
+        return Err(CommandError::invalid("No valid crops processed"));
     }
 
     Ok(output_paths)
@@ -479,8 +2054,10 @@ pub struct ThumbnailResult {
 
 /// Generate thumbnails for multiple images in parallel
 #[tauri::command]
-pub fn get_thumbnails_batch(payload: GetThumbnailsBatchPayload) -> Result<Vec<ThumbnailResult>, String> {
-    let size = payload.size.unwrap_or(THUMB_SIZE).min(512);
+pub fn get_thumbnails_batch(
+    payload: GetThumbnailsBatchPayload,
+) -> Result<Vec<ThumbnailResult>, CommandError> {
+    let size = payload.size.unwrap_or(THUMB_SIZE).min(THUMB_MAX_SIZE);
     let cache_dir = thumbnail_cache_dir()?;
 
     let results: Vec<ThumbnailResult> = payload
@@ -488,7 +2065,7 @@ pub fn get_thumbnails_batch(payload: GetThumbnailsBatchPayload) -> Result<Vec<Th
         .par_iter()
         .map(|path_str| {
             let path = PathBuf::from(path_str);
-            
+
             if !path.exists() || !path.is_file() {
                 return ThumbnailResult {
                     path: path_str.clone(),
@@ -498,10 +2075,10 @@ pub fn get_thumbnails_batch(payload: GetThumbnailsBatchPayload) -> Result<Vec<Th
             }
 
             // Try to get from cache
-            match thumbnail_cache_key(&path, size) {
+            match thumbnail_cache_key(&path, size, None, FilterType::Triangle, None) {
                 Ok(key) => {
                     let cache_path = cache_dir.join(format!("{}.jpg", key));
-                    
+
                     if cache_path.exists() && cache_path.is_file() {
                         if let Ok(mut f) = fs::File::open(&cache_path) {
                             let mut buf = Vec::new();
@@ -517,17 +2094,20 @@ pub fn get_thumbnails_batch(payload: GetThumbnailsBatchPayload) -> Result<Vec<Th
                     }
 
                     // Generate thumbnail
-                    match image::open(&path) {
+                    match thumbnail_source(&path, size) {
                         Ok(img) => {
                             let thumb = img.resize(size, size, FilterType::Triangle);
                             let mut buf = Vec::new();
-                            
-                            if thumb.write_to(&mut Cursor::new(&mut buf), ImageFormat::Jpeg).is_ok() {
+
+                            if thumb
+                                .write_to(&mut Cursor::new(&mut buf), ImageFormat::Jpeg)
+                                .is_ok()
+                            {
                                 // Try to cache
                                 if let Ok(mut f) = fs::File::create(&cache_path) {
                                     let _ = f.write_all(&buf);
                                 }
-                                
+
                                 let b64 = BASE64.encode(&buf);
                                 ThumbnailResult {
                                     path: path_str.clone(),
@@ -552,7 +2132,7 @@ pub fn get_thumbnails_batch(payload: GetThumbnailsBatchPayload) -> Result<Vec<Th
                 Err(e) => ThumbnailResult {
                     path: path_str.clone(),
                     data_url: None,
-                    error: Some(e),
+                    error: Some(e.to_string()),
                 },
             }
         })
@@ -560,3 +2140,124 @@ pub fn get_thumbnails_batch(payload: GetThumbnailsBatchPayload) -> Result<Vec<Th
 
     Ok(results)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORIENTATIONS: [Orientation; 8] = [
+        Orientation::NoTransforms,
+        Orientation::Rotate90,
+        Orientation::Rotate180,
+        Orientation::Rotate270,
+        Orientation::FlipHorizontal,
+        Orientation::FlipVertical,
+        Orientation::Rotate90FlipH,
+        Orientation::Rotate270FlipH,
+    ];
+
+    /// 5x3 image where every pixel is unique, so any misplaced crop shows up.
+    fn raw_image() -> image::DynamicImage {
+        image::DynamicImage::ImageRgb8(image::RgbImage::from_fn(5, 3, |x, y| {
+            image::Rgb([x as u8, y as u8, (x * 10 + y) as u8])
+        }))
+    }
+
+    #[test]
+    fn remap_matches_cropping_the_oriented_preview() {
+        let raw = raw_image();
+        for orientation in ORIENTATIONS {
+            let mut shown = raw.clone();
+            shown.apply_orientation(orientation);
+            let display = (shown.width(), shown.height());
+            let rect = (1, 0, 2, display.1.min(2));
+
+            let expected = shown.crop_imm(rect.0, rect.1, rect.2, rect.3).to_rgb8();
+            let (x, y, w, h) = remap_oriented_rect(rect, display, (5, 3), orientation).unwrap();
+            let mut actual = raw.crop_imm(x, y, w, h);
+            actual.apply_orientation(orientation);
+            assert_eq!(actual.to_rgb8(), expected, "{:?}", orientation);
+        }
+    }
+
+    #[test]
+    fn remap_rotate90_swaps_axes() {
+        // Displayed 3x5; its top-left 1x2 strip is the left end of the raw image's bottom row.
+        let raw = remap_oriented_rect((0, 0, 1, 2), (3, 5), (5, 3), Orientation::Rotate90);
+        assert_eq!(raw.unwrap(), (0, 2, 2, 1));
+    }
+
+    #[test]
+    fn remap_rejects_out_of_bounds_rect() {
+        for orientation in ORIENTATIONS {
+            let mut shown = raw_image();
+            shown.apply_orientation(orientation);
+            let display = (shown.width(), shown.height());
+            let rect = (display.0 - 1, 0, 2, 1);
+            let err = remap_oriented_rect(rect, display, (5, 3), orientation).unwrap_err();
+            assert_eq!(err.code(), "invalid_input");
+            assert_eq!(err.message(), "Crop region exceeds image bounds");
+        }
+    }
+
+    #[test]
+    fn remap_rejects_mismatched_display_size() {
+        let result = remap_oriented_rect((0, 0, 1, 1), (5, 3), (5, 3), Orientation::Rotate90);
+        assert!(result
+            .unwrap_err()
+            .message()
+            .starts_with("Displayed size 5x3"));
+    }
+
+    #[test]
+    fn preview_data_url_mime_matches_requested_format() {
+        let img = raw_image();
+        for (requested, prefix, expected) in [
+            ("jpeg", "data:image/jpeg;base64,", ImageFormat::Jpeg),
+            ("webp", "data:image/webp;base64,", ImageFormat::WebP),
+        ] {
+            let format = PreviewFormat::parse(Some(requested)).unwrap();
+            let url = format.data_url(&format.encode(&img).unwrap());
+            let encoded = url.strip_prefix(prefix).expect(requested);
+            let bytes = BASE64.decode(encoded).unwrap();
+            assert_eq!(
+                image::guess_format(&bytes).unwrap(),
+                expected,
+                "{}",
+                requested
+            );
+        }
+    }
+
+    #[test]
+    fn save_edited_copies_caption_only_when_asked() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.png");
+        let img = raw_image();
+        img.save(&path).unwrap();
+        fs::write(path.with_extension("txt"), "1girl, smile\n").unwrap();
+        for with_caption in [true, false] {
+            let out = save_edited(&img, &path, "edit", true, with_caption)
+                .unwrap()
+                .unwrap();
+            let caption = Path::new(&out).with_extension("txt");
+            assert_eq!(caption.is_file(), with_caption, "{}", out);
+        }
+    }
+
+    #[test]
+    fn save_edited_never_overwrites_unwritable_formats() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.heic");
+        fs::write(&path, b"original").unwrap();
+        let img = raw_image();
+        let err = save_edited(&img, &path, "edit", false, false).unwrap_err();
+        assert_eq!(err.code(), "invalid_input");
+        assert_eq!(fs::read(&path).unwrap(), b"original");
+        let out = save_edited(&img, &path, "edit", true, false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(Path::new(&out), dir.path().join("a_1_edit.png"));
+        assert_eq!(image::open(&out).unwrap().to_rgb8(), img.to_rgb8());
+    }
+}
@@ -6,7 +6,10 @@ use serde::{Deserialize, Serialize};
 use std::io::Cursor;
 use std::path::PathBuf;
 
-const DEFAULT_BASE_URL: &str = "http://localhost:1234";
+use super::error::CommandError;
+use super::images::open_image;
+
+pub const DEFAULT_BASE_URL: &str = "http://localhost:1234";
 
 #[derive(Debug, Deserialize)]
 #[allow(dead_code)]
@@ -21,6 +24,21 @@ fn default_base_url() -> String {
     DEFAULT_BASE_URL.to_string()
 }
 
+/// Apply optional bearer token and custom headers to a request (for servers behind an auth proxy).
+pub fn apply_auth(
+    mut request: reqwest::RequestBuilder,
+    api_key: Option<&str>,
+    headers: Option<&[(String, String)]>,
+) -> reqwest::RequestBuilder {
+    if let Some(key) = api_key.map(str::trim).filter(|k| !k.is_empty()) {
+        request = request.bearer_auth(key);
+    }
+    for (name, value) in headers.unwrap_or_default() {
+        request = request.header(name.as_str(), value.as_str());
+    }
+    request
+}
+
 #[derive(Debug, Deserialize)]
 pub struct TestConnectionPayload {
     #[serde(default = "default_base_url")]
@@ -38,11 +56,16 @@ pub struct ConnectionStatus {
 #[tauri::command]
 pub async fn test_lm_studio_connection(
     payload: TestConnectionPayload,
-) -> Result<ConnectionStatus, String> {
+) -> Result<ConnectionStatus, CommandError> {
     let url = format!("{}/v1/models", payload.base_url.trim_end_matches('/'));
 
     let client = reqwest::Client::new();
-    let response = match client.get(&url).timeout(std::time::Duration::from_secs(5)).send().await {
+    let response = match client
+        .get(&url)
+        .timeout(std::time::Duration::from_secs(5))
+        .send()
+        .await
+    {
         Ok(r) => r,
         Err(e) => {
             return Ok(ConnectionStatus {
@@ -71,7 +94,7 @@ pub async fn test_lm_studio_connection(
         id: String,
     }
 
-    let models_response: ModelsResponse = response.json().await.map_err(|e| e.to_string())?;
+    let models_response: ModelsResponse = response.json().await?;
     let models: Vec<String> = models_response.data.into_iter().map(|m| m.id).collect();
 
     Ok(ConnectionStatus {
@@ -97,16 +120,44 @@ pub struct GenerateCaptionPayload {
     /// If set, resize image so longest side is at most this (reduces payload and inference time).
     #[serde(default)]
     pub max_image_dimension: Option<u32>,
+    /// Bearer token for servers behind an authenticating proxy (e.g. remote Ollama).
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// Extra request headers as (name, value) pairs.
+    #[serde(default)]
+    pub headers: Option<Vec<(String, String)>>,
+    /// Retries on connection errors and 5xx responses (default 0, max 10). Timeouts are not
+    /// retried, since each attempt would wait the full timeout again.
+    #[serde(default)]
+    pub max_retries: Option<u32>,
+    /// Delay before the first retry in ms, doubled on each further retry (default 500).
+    #[serde(default)]
+    pub retry_delay_ms: Option<u64>,
+    /// Caption only this part of the image. Cropped in memory; no file is written.
+    #[serde(default)]
+    pub region: Option<CaptionRegion>,
+}
+
+/// Pixel rectangle of the decoded image.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct CaptionRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
 }
 
-fn default_max_tokens() -> u32 {
+pub fn default_max_tokens() -> u32 {
     300
 }
 
 const DEFAULT_TIMEOUT_SECS: u32 = 120;
 const MAX_TIMEOUT_SECS: u32 = 600;
+const DEFAULT_MAX_RETRIES: u32 = 0;
+const MAX_RETRIES: u32 = 10;
+const DEFAULT_RETRY_DELAY_MS: u64 = 500;
 
-fn default_timeout_secs() -> u32 {
+pub fn default_timeout_secs() -> u32 {
     DEFAULT_TIMEOUT_SECS
 }
 
@@ -121,7 +172,7 @@ pub struct CaptionResult {
 #[tauri::command]
 pub async fn generate_caption_lm_studio(
     payload: GenerateCaptionPayload,
-) -> Result<CaptionResult, String> {
+) -> Result<CaptionResult, CommandError> {
     let path = PathBuf::from(&payload.image_path);
     if !path.exists() || !path.is_file() {
         return Ok(CaptionResult {
@@ -133,7 +184,17 @@ pub async fn generate_caption_lm_studio(
 
     // Decode image so we can normalize to JPEG (LM Studio vision often only accepts JPEG).
     // Optionally resize to reduce payload and inference time.
-    let img = image::open(&path).map_err(|e| e.to_string())?;
+    let mut img = open_image(&path)?;
+    if let Some(r) = payload.region {
+        let fits = r.x as u64 + r.width as u64 <= img.width() as u64
+            && r.y as u64 + r.height as u64 <= img.height() as u64;
+        if r.width == 0 || r.height == 0 || !fits {
+            return Err(CommandError::invalid(
+                "Caption region is empty or outside the image",
+            ));
+        }
+        img = img.crop_imm(r.x, r.y, r.width, r.height);
+    }
     let (w, h) = (img.width(), img.height());
 
     let img = if let Some(max_dim) = payload.max_image_dimension.filter(|&d| d > 0) {
@@ -153,8 +214,7 @@ pub async fn generate_caption_lm_studio(
     };
 
     let mut buf = Vec::new();
-    img.write_to(&mut Cursor::new(&mut buf), ImageFormat::Jpeg)
-        .map_err(|e| e.to_string())?;
+    img.write_to(&mut Cursor::new(&mut buf), ImageFormat::Jpeg)?;
     let base64_image = BASE64.encode(&buf);
     let data_url = format!("data:image/jpeg;base64,{}", base64_image);
 
@@ -188,54 +248,66 @@ pub async fn generate_caption_lm_studio(
         payload.base_url.trim_end_matches('/')
     );
 
-    let timeout_secs = payload.timeout_secs.min(MAX_TIMEOUT_SECS).max(1);
+    let timeout_secs = payload.timeout_secs.clamp(1, MAX_TIMEOUT_SECS);
     let client = reqwest::Client::new();
     let do_request = || {
-        client
-            .post(&url)
+        let request = apply_auth(
+            client.post(&url),
+            payload.api_key.as_deref(),
+            payload.headers.as_deref(),
+        );
+        request
             .header("Content-Type", "application/json")
             .json(&request_body)
             .timeout(std::time::Duration::from_secs(timeout_secs as u64))
             .send()
     };
 
-    let response = match do_request().await {
-        Ok(r) => r,
-        Err(e) => {
-            let err_str = e.to_string();
-            let is_timeout = err_str.contains("timed out") || err_str.contains("timeout");
-            if !is_timeout {
-                return Ok(CaptionResult {
-                    success: false,
-                    caption: String::new(),
-                    error: Some(format!("Request failed: {}", e)),
-                });
+    let max_attempts = payload
+        .max_retries
+        .unwrap_or(DEFAULT_MAX_RETRIES)
+        .min(MAX_RETRIES)
+        + 1;
+    let retry_delay_ms = payload.retry_delay_ms.unwrap_or(DEFAULT_RETRY_DELAY_MS);
+    let failure = |error: String| CaptionResult {
+        success: false,
+        caption: String::new(),
+        error: Some(error),
+    };
+
+    // Retry transient failures (connection errors, 5xx) with exponential backoff. 4xx responses,
+    // timeouts and body errors are returned immediately since resending won't help soon.
+    let mut attempt = 0u32;
+    let response = loop {
+        attempt += 1;
+        match do_request().await {
+            Ok(r) if r.status().is_server_error() && attempt < max_attempts => {}
+            Ok(r) => break r,
+            Err(e) if e.is_connect() && attempt < max_attempts => {}
+            Err(e) if e.is_timeout() => {
+                return Ok(failure(format!(
+                    "Request timed out after {} seconds (tried {} times). Try a larger timeout in settings or use smaller images.",
+                    timeout_secs, attempt
+                )));
             }
-            // Retry once on timeout
-            match do_request().await {
-                Ok(r) => r,
-                Err(_) => {
-                    return Ok(CaptionResult {
-                        success: false,
-                        caption: String::new(),
-                        error: Some(format!(
-                            "Request timed out after {} seconds (tried 2 times). Try a larger timeout in settings or use smaller images.",
-                            timeout_secs
-                        )),
-                    });
-                }
+            Err(e) => {
+                return Ok(failure(format!(
+                    "Request failed after {} attempt(s): {}",
+                    attempt, e
+                )));
             }
         }
+        let backoff = retry_delay_ms.saturating_mul(1u64 << (attempt - 1).min(16));
+        tokio::time::sleep(std::time::Duration::from_millis(backoff)).await;
     };
 
     if !response.status().is_success() {
         let status = response.status();
         let body = response.text().await.unwrap_or_default();
-        return Ok(CaptionResult {
-            success: false,
-            caption: String::new(),
-            error: Some(format!("Server error {}: {}", status, body)),
-        });
+        return Ok(failure(format!(
+            "Server error {} after {} attempt(s): {}",
+            status, attempt, body
+        )));
     }
 
     #[derive(Deserialize)]
@@ -300,6 +372,17 @@ pub struct BatchCaptionPayload {
     /// Max concurrent requests (1 = sequential, 2–3 recommended).
     #[serde(default = "default_batch_concurrency")]
     pub concurrency: u32,
+    /// Bearer token for servers behind an authenticating proxy (e.g. remote Ollama).
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// Extra request headers as (name, value) pairs.
+    #[serde(default)]
+    pub headers: Option<Vec<(String, String)>>,
+    /// Per-image retries on transient failures (see GenerateCaptionPayload).
+    #[serde(default)]
+    pub max_retries: Option<u32>,
+    #[serde(default)]
+    pub retry_delay_ms: Option<u64>,
 }
 
 #[derive(Debug, Serialize, Clone)]
@@ -315,8 +398,8 @@ pub struct BatchCaptionResult {
 #[tauri::command]
 pub async fn generate_captions_batch(
     payload: BatchCaptionPayload,
-) -> Result<Vec<BatchCaptionResult>, String> {
-    let concurrency = payload.concurrency.max(1).min(8) as usize;
+) -> Result<Vec<BatchCaptionResult>, CommandError> {
+    let concurrency = payload.concurrency.clamp(1, 8) as usize;
 
     let base_url = payload.base_url.clone();
     let model = payload.model.clone();
@@ -324,6 +407,10 @@ pub async fn generate_captions_batch(
     let max_tokens = payload.max_tokens;
     let timeout_secs = payload.timeout_secs;
     let max_image_dimension = payload.max_image_dimension;
+    let api_key = payload.api_key.clone();
+    let headers = payload.headers.clone();
+    let max_retries = payload.max_retries;
+    let retry_delay_ms = payload.retry_delay_ms;
 
     let futures = payload
         .image_paths
@@ -341,6 +428,11 @@ pub async fn generate_captions_batch(
                 max_tokens,
                 timeout_secs,
                 max_image_dimension,
+                api_key: api_key.clone(),
+                headers: headers.clone(),
+                max_retries,
+                retry_delay_ms,
+                region: None,
             };
             async move {
                 let result = generate_caption_lm_studio(single_payload).await;
@@ -348,30 +440,29 @@ pub async fn generate_captions_batch(
             }
         });
 
-    let mut completed: Vec<(usize, String, Result<CaptionResult, String>)> = stream::iter(futures)
-        .buffer_unordered(concurrency)
-        .collect()
-        .await;
+    let mut completed: Vec<(usize, String, Result<CaptionResult, CommandError>)> =
+        stream::iter(futures)
+            .buffer_unordered(concurrency)
+            .collect()
+            .await;
 
     completed.sort_by_key(|(i, _, _)| *i);
 
     let results: Vec<BatchCaptionResult> = completed
         .into_iter()
-        .map(|(_, path, result)| {
-            match result {
-                Ok(r) => BatchCaptionResult {
-                    path,
-                    success: r.success,
-                    caption: r.caption,
-                    error: r.error,
-                },
-                Err(e) => BatchCaptionResult {
-                    path,
-                    success: false,
-                    caption: String::new(),
-                    error: Some(e),
-                },
-            }
+        .map(|(_, path, result)| match result {
+            Ok(r) => BatchCaptionResult {
+                path,
+                success: r.success,
+                caption: r.caption,
+                error: r.error,
+            },
+            Err(e) => BatchCaptionResult {
+                path,
+                success: false,
+                caption: String::new(),
+                error: Some(e.to_string()),
+            },
         })
         .collect();
 
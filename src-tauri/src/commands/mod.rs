@@ -1,10 +1,15 @@
 pub mod batch_rename;
+pub mod caption_provider;
 pub mod captions;
 pub mod crop_status;
 pub mod detect;
+pub mod error;
 pub mod export;
+pub mod formats;
 pub mod images;
 pub mod lm_studio;
 pub mod ollama;
 pub mod project;
 pub mod ratings;
+pub mod sidecar;
+pub mod walk;
@@ -1,28 +1,66 @@
 //! Ollama provider: OpenAI-compatible API at http://localhost:11434/v1.
-//! Listing models uses GET /api/tags (base URL without /v1).
+//! Listing models uses GET /api/tags and downloading them POST /api/pull (base URL without /v1).
 //! Generation reuses lm_studio commands with Ollama base URL and model.
+//! Remote hosts behind a proxy can pass `api_key` / `headers`; both are honored by the lm_studio
+//! generation commands as well.
 
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
 
-const DEFAULT_OLLAMA_BASE_URL: &str = "http://localhost:11434/v1";
+use super::error::CommandError;
+use super::lm_studio::apply_auth;
+
+pub const DEFAULT_OLLAMA_BASE_URL: &str = "http://localhost:11434/v1";
+const DEFAULT_TAGS_TIMEOUT_SECS: u64 = 10;
 
 #[derive(Debug, Deserialize)]
 pub struct TestOllamaConnectionPayload {
     #[serde(default = "default_ollama_base_url")]
     pub base_url: String,
+    /// Sent as `Authorization: Bearer <api_key>` (for Ollama behind an authenticating proxy).
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// Extra request headers as (name, value) pairs.
+    #[serde(default)]
+    pub headers: Option<Vec<(String, String)>>,
+    /// Request timeout in seconds (default 10).
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
 }
 
 fn default_ollama_base_url() -> String {
     DEFAULT_OLLAMA_BASE_URL.to_string()
 }
 
+/// Host root for native `/api/*` endpoints: the configured URL without a trailing `/v1`
+/// (Ollama URLs are configured as the OpenAI-compatible `.../v1` endpoint).
+pub fn api_root(base_url: &str) -> String {
+    let base = base_url.trim_end_matches('/');
+    base.strip_suffix("/v1")
+        .unwrap_or(base)
+        .trim_end_matches('/')
+        .to_string()
+}
+
 #[derive(Debug, Serialize)]
 pub struct ConnectionStatus {
     pub connected: bool,
     pub models: Vec<String>,
+    /// Same models as `models`, with size and quantization for picking one that fits the GPU.
+    pub models_detailed: Vec<OllamaModel>,
     pub error: Option<String>,
 }
 
+#[derive(Debug, Serialize)]
+pub struct OllamaModel {
+    pub name: String,
+    pub size_bytes: Option<u64>,
+    /// e.g. "Q4_K_M"
+    pub quantization: Option<String>,
+    pub family: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 struct OllamaTagsResponse {
     models: Option<Vec<OllamaModelInfo>>,
@@ -31,6 +69,18 @@ struct OllamaTagsResponse {
 #[derive(Debug, Deserialize)]
 struct OllamaModelInfo {
     name: String,
+    #[serde(default)]
+    size: Option<u64>,
+    #[serde(default)]
+    details: Option<OllamaModelDetails>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaModelDetails {
+    #[serde(default)]
+    quantization_level: Option<String>,
+    #[serde(default)]
+    family: Option<String>,
 }
 
 /// Test connection to Ollama and list available models (including vision models like llava).
@@ -39,18 +89,21 @@ struct OllamaModelInfo {
 #[tauri::command]
 pub async fn test_ollama_connection(
     payload: TestOllamaConnectionPayload,
-) -> Result<ConnectionStatus, String> {
-    let base = payload.base_url.trim_end_matches('/');
-    let tags_url = if base.ends_with("/v1") {
-        format!("{}/api/tags", base.trim_end_matches("/v1").trim_end_matches('/'))
-    } else {
-        format!("{}/api/tags", base)
-    };
+) -> Result<ConnectionStatus, CommandError> {
+    let tags_url = format!("{}/api/tags", api_root(&payload.base_url));
 
+    let timeout_secs = payload
+        .timeout_secs
+        .unwrap_or(DEFAULT_TAGS_TIMEOUT_SECS)
+        .max(1);
     let client = reqwest::Client::new();
-    let response = match client
-        .get(&tags_url)
-        .timeout(std::time::Duration::from_secs(10))
+    let request = apply_auth(
+        client.get(&tags_url),
+        payload.api_key.as_deref(),
+        payload.headers.as_deref(),
+    );
+    let response = match request
+        .timeout(std::time::Duration::from_secs(timeout_secs))
         .send()
         .await
     {
@@ -59,6 +112,7 @@ pub async fn test_ollama_connection(
             return Ok(ConnectionStatus {
                 connected: false,
                 models: Vec::new(),
+                models_detailed: Vec::new(),
                 error: Some(format!("Connection failed: {}", e)),
             });
         }
@@ -68,21 +122,148 @@ pub async fn test_ollama_connection(
         return Ok(ConnectionStatus {
             connected: false,
             models: Vec::new(),
+            models_detailed: Vec::new(),
             error: Some(format!("Ollama returned status: {}", response.status())),
         });
     }
 
-    let tags_response: OllamaTagsResponse = response.json().await.map_err(|e| e.to_string())?;
-    let models: Vec<String> = tags_response
+    let tags_response: OllamaTagsResponse = response.json().await?;
+    let models_detailed: Vec<OllamaModel> = tags_response
         .models
         .unwrap_or_default()
         .into_iter()
-        .map(|m| m.name)
+        .map(|m| {
+            let details = m.details;
+            OllamaModel {
+                name: m.name,
+                size_bytes: m.size,
+                quantization: details.as_ref().and_then(|d| d.quantization_level.clone()),
+                family: details.and_then(|d| d.family),
+            }
+        })
         .collect();
+    let models: Vec<String> = models_detailed.iter().map(|m| m.name.clone()).collect();
 
     Ok(ConnectionStatus {
         connected: true,
         models,
+        models_detailed,
         error: None,
     })
 }
+
+const PULL_PROGRESS_EVENT: &str = "ollama-pull-progress";
+const PULL_CONNECT_TIMEOUT_SECS: u64 = 10;
+
+#[derive(Debug, Deserialize)]
+pub struct PullOllamaModelPayload {
+    #[serde(default = "default_ollama_base_url")]
+    pub base_url: String,
+    pub model: String,
+    #[serde(default)]
+    pub api_key: Option<String>,
+    #[serde(default)]
+    pub headers: Option<Vec<(String, String)>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct PullProgress {
+    status: String,
+    /// Bytes of the current layer; absent for status-only lines like "verifying sha256 digest".
+    completed: Option<u64>,
+    total: Option<u64>,
+}
+
+/// One NDJSON line from /api/pull.
+#[derive(Debug, Deserialize)]
+struct PullStatusLine {
+    #[serde(default)]
+    status: String,
+    #[serde(default)]
+    completed: Option<u64>,
+    #[serde(default)]
+    total: Option<u64>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+/// Handle one streamed line; Ok(true) once Ollama reports success.
+fn handle_pull_line(app: &AppHandle, line: &[u8]) -> Result<bool, CommandError> {
+    let line = String::from_utf8_lossy(line);
+    let line = line.trim();
+    if line.is_empty() {
+        return Ok(false);
+    }
+    let parsed: PullStatusLine = serde_json::from_str(line)
+        .map_err(|e| CommandError::from(e).context("Unexpected pull response"))?;
+    if let Some(error) = parsed.error {
+        return Err(CommandError::Network(format!("Ollama: {}", error)));
+    }
+    let done = parsed.status == "success";
+    let _ = app.emit(
+        PULL_PROGRESS_EVENT,
+        PullProgress {
+            status: parsed.status,
+            completed: parsed.completed,
+            total: parsed.total,
+        },
+    );
+    Ok(done)
+}
+
+/// Download a model into Ollama (POST /api/pull), emitting `ollama-pull-progress` for each
+/// streamed status line. Returns once Ollama reports success.
+#[tauri::command]
+pub async fn pull_ollama_model(
+    app: AppHandle,
+    payload: PullOllamaModelPayload,
+) -> Result<(), CommandError> {
+    let model = payload.model.trim();
+    if model.is_empty() {
+        return Err(CommandError::invalid("Model name is required"));
+    }
+    let url = format!("{}/api/pull", api_root(&payload.base_url));
+    let client = reqwest::Client::builder()
+        .connect_timeout(Duration::from_secs(PULL_CONNECT_TIMEOUT_SECS))
+        .build()?;
+    // `name` is the field older Ollama versions expect.
+    let body = serde_json::json!({ "model": model, "name": model, "stream": true });
+    let mut response = apply_auth(
+        client.post(&url).json(&body),
+        payload.api_key.as_deref(),
+        payload.headers.as_deref(),
+    )
+    .send()
+    .await
+    .map_err(|e| CommandError::from(e).context("Connection failed"))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let text = response.text().await.unwrap_or_default();
+        return Err(CommandError::Network(format!(
+            "Ollama returned status {}: {}",
+            status,
+            text.trim()
+        )));
+    }
+
+    // Chunks don't align with lines, so buffer until each newline.
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut succeeded = false;
+    while let Some(chunk) = response.chunk().await? {
+        buffer.extend_from_slice(&chunk);
+        while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=pos).collect();
+            succeeded |= handle_pull_line(&app, &line)?;
+        }
+    }
+    succeeded |= handle_pull_line(&app, &buffer)?;
+
+    if succeeded {
+        Ok(())
+    } else {
+        Err(CommandError::Network(
+            "Pull ended before Ollama reported success".to_string(),
+        ))
+    }
+}
@@ -1,4 +1,7 @@
 use image::ImageReader;
+use notify::event::ModifyKind;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use once_cell::sync::Lazy;
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
@@ -6,44 +9,34 @@ use std::collections::HashMap;
 use std::fs;
 use std::io::Read;
 use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
 use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use tauri::{AppHandle, Emitter};
-use walkdir::WalkDir;
 
-use super::ratings::{load_ratings, ImageRating};
+use super::captions::parse_tags;
+use super::error::CommandError;
+use super::formats::{has_image_sibling, is_image_path};
+use super::images::{open_image, validate_image};
+use super::ratings::{load_ratings, normalize_rel, ImageRating, RatingLookup};
+use super::sidecar::{self, CaptionExt};
+use super::walk::{is_hidden_under, ProjectWalk};
 
 const PROGRESS_EVENT: &str = "project-load-progress";
 
-const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "webp", "gif", "bmp"];
-
-fn is_image_path(path: &Path) -> bool {
-    let ext = path
-        .extension()
-        .and_then(|e| e.to_str())
-        .map(|e| e.to_lowercase());
-    ext.as_ref()
-        .map(|e| IMAGE_EXTENSIONS.contains(&e.as_str()))
-        .unwrap_or(false)
-}
-
-/// Get the caption file path for an image (same name, .txt extension).
-fn caption_path_for(image_path: &Path) -> PathBuf {
-    image_path.with_extension("txt")
-}
-
-/// Parse comma-separated tags from raw caption text.
-fn parse_tags(raw: &str) -> Vec<String> {
-    raw.split(',')
-        .map(|s| s.trim().to_string())
-        .filter(|s| !s.is_empty())
-        .collect()
-}
-
 #[derive(Debug, Deserialize)]
 pub struct OpenProjectPayload {
     pub root_path: String,
     #[serde(default = "default_false")]
     pub include_dimensions: bool,
+    /// Descend into symlinked folders. WalkDir detects symlink cycles and skips the looping entry,
+    /// but links can still pull in large trees from outside the project.
+    #[serde(default)]
+    pub follow_symlinks: bool,
+    /// Globs (relative path, case-insensitive) of images to leave out, e.g. `raw/**`. Hidden
+    /// files and folders are always skipped.
+    #[serde(default)]
+    pub ignore_patterns: Option<Vec<String>>,
 }
 
 fn default_false() -> bool {
@@ -75,39 +68,34 @@ struct ProjectLoadProgress {
 /// Opens a project at the given root path. Scans recursively for image files.
 /// Emits progress events as images are discovered.
 #[tauri::command]
-pub fn open_project(app: AppHandle, payload: OpenProjectPayload) -> Result<Vec<ImageEntry>, String> {
+pub fn open_project(
+    app: AppHandle,
+    payload: OpenProjectPayload,
+) -> Result<Vec<ImageEntry>, CommandError> {
     let root = PathBuf::from(&payload.root_path);
     if !root.exists() {
-        return Err("Folder does not exist".to_string());
+        return Err(CommandError::NotFound("Folder does not exist".to_string()));
     }
     if !root.is_dir() {
-        return Err("Path is not a folder".to_string());
+        return Err(CommandError::invalid("Path is not a folder"));
     }
 
-    let canonical_root = root.canonicalize().map_err(|e| e.to_string())?;
+    let canonical_root = root.canonicalize()?;
     let ratings_data = load_ratings(&payload.root_path);
     let mut entries = Vec::new();
 
-    for entry in WalkDir::new(&canonical_root)
-        .follow_links(false)
-        .into_iter()
-        .filter_map(|e| e.ok())
-    {
-        let path = entry.path();
-        if !path.is_file() || !is_image_path(path) {
-            continue;
-        }
-        let path_buf = path.to_path_buf();
+    let walker = ProjectWalk::new(payload.follow_symlinks, payload.ignore_patterns.as_deref())?;
+    for path_buf in walker.images(&canonical_root).images {
         let path_str = path_buf
             .to_str()
-            .ok_or("Invalid path encoding")?
+            .ok_or_else(|| CommandError::invalid("Invalid path encoding"))?
             .to_string();
         let relative = path_buf
             .strip_prefix(&canonical_root)
             .unwrap_or_else(|_| path_buf.as_path());
         let relative_path = relative
             .to_str()
-            .ok_or("Invalid path encoding")?
+            .ok_or_else(|| CommandError::invalid("Invalid path encoding"))?
             .replace('\\', "/");
         let filename = path_buf
             .file_name()
@@ -117,14 +105,9 @@ pub fn open_project(app: AppHandle, payload: OpenProjectPayload) -> Result<Vec<I
         let id = path_str.clone();
 
         // Read caption file if exists
-        let caption_path = caption_path_for(&path_buf);
-        let (has_caption, tags) = if caption_path.exists() {
-            match fs::read_to_string(&caption_path) {
-                Ok(raw) => (true, parse_tags(&raw)),
-                Err(_) => (false, Vec::new()),
-            }
-        } else {
-            (false, Vec::new())
+        let (has_caption, tags) = match sidecar::read_for_image(&path_buf) {
+            Some(raw) => (true, parse_tags(&raw)),
+            None => (false, Vec::new()),
         };
 
         // Get rating from loaded ratings data
@@ -146,7 +129,10 @@ pub fn open_project(app: AppHandle, payload: OpenProjectPayload) -> Result<Vec<I
         let width = if width > 0 { Some(width) } else { None };
         let height = if height > 0 { Some(height) } else { None };
 
-        let file_size = fs::metadata(&path_buf).ok().map(|m| m.len()).filter(|&n| n > 0);
+        let file_size = fs::metadata(&path_buf)
+            .ok()
+            .map(|m| m.len())
+            .filter(|&n| n > 0);
 
         entries.push(ImageEntry {
             id,
@@ -163,12 +149,22 @@ pub fn open_project(app: AppHandle, payload: OpenProjectPayload) -> Result<Vec<I
 
         // Emit progress every 25 images (more frequent for better UX)
         if entries.len() % 25 == 0 {
-            let _ = app.emit(PROGRESS_EVENT, ProjectLoadProgress { count: entries.len() });
+            let _ = app.emit(
+                PROGRESS_EVENT,
+                ProjectLoadProgress {
+                    count: entries.len(),
+                },
+            );
         }
     }
 
     // Emit final count
-    let _ = app.emit(PROGRESS_EVENT, ProjectLoadProgress { count: entries.len() });
+    let _ = app.emit(
+        PROGRESS_EVENT,
+        ProjectLoadProgress {
+            count: entries.len(),
+        },
+    );
 
     entries.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
     Ok(entries)
@@ -186,28 +182,21 @@ pub struct FindDuplicatesResult {
 
 /// Find duplicate images by file content hash (SHA-256). Returns groups of relative paths.
 #[tauri::command]
-pub fn find_duplicates(payload: FindDuplicatesPayload) -> Result<FindDuplicatesResult, String> {
+pub fn find_duplicates(
+    payload: FindDuplicatesPayload,
+) -> Result<FindDuplicatesResult, CommandError> {
     let root = PathBuf::from(&payload.root_path);
     if !root.exists() || !root.is_dir() {
-        return Err("Folder does not exist".to_string());
+        return Err(CommandError::NotFound("Folder does not exist".to_string()));
     }
-    let canonical_root = root.canonicalize().map_err(|e| e.to_string())?;
+    let canonical_root = root.canonicalize()?;
 
-    // Collect all image paths first
-    let image_paths: Vec<PathBuf> = WalkDir::new(&root)
-        .follow_links(false)
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|entry| {
-            let path = entry.path();
-            path.is_file() && is_image_path(path)
-        })
-        .map(|entry| entry.path().to_path_buf())
-        .collect();
+    // Collect all image paths first (same walk as the project, so .loraignore applies)
+    let image_paths = ProjectWalk::default().images(&canonical_root).images;
 
     // Parallel hash computation
     let hash_to_paths: Mutex<HashMap<String, Vec<String>>> = Mutex::new(HashMap::new());
-    
+
     image_paths.par_iter().for_each(|path| {
         // Hash the file
         if let Ok(mut file) = fs::File::open(path) {
@@ -223,19 +212,15 @@ pub fn find_duplicates(payload: FindDuplicatesPayload) -> Result<FindDuplicatesR
             let hash_hex = hex::encode(hasher.finalize());
 
             // Get relative path
-            let relative = path
-                .strip_prefix(&canonical_root)
-                .unwrap_or(path);
+            let relative = path.strip_prefix(&canonical_root).unwrap_or(path);
             let rel_str = relative
                 .to_str()
                 .map(|s| s.replace('\\', "/"))
                 .unwrap_or_default();
-            
+
             if !rel_str.is_empty() {
                 let mut map = hash_to_paths.lock().unwrap();
-                map.entry(hash_hex)
-                    .or_default()
-                    .push(rel_str);
+                map.entry(hash_hex).or_default().push(rel_str);
             }
         }
     });
@@ -249,6 +234,136 @@ pub fn find_duplicates(payload: FindDuplicatesPayload) -> Result<FindDuplicatesR
     Ok(FindDuplicatesResult { groups })
 }
 
+#[derive(Debug, Deserialize)]
+pub struct FindDuplicateImagesPayload {
+    pub root_path: String,
+    /// Max Hamming distance (0–64) between perceptual hashes to count as a near-duplicate.
+    #[serde(default = "default_similarity_threshold")]
+    pub threshold: u32,
+}
+
+fn default_similarity_threshold() -> u32 {
+    10
+}
+
+#[derive(Debug, Serialize)]
+pub struct PairDistance {
+    pub a: String,
+    pub b: String,
+    pub distance: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SimilarImageGroup {
+    pub paths: Vec<String>,
+    /// Distances for every pair within the threshold (not all pairs in the group).
+    pub distances: Vec<PairDistance>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FindDuplicateImagesResult {
+    pub groups: Vec<SimilarImageGroup>,
+}
+
+/// 64-bit difference hash: shrink to 9x8 grayscale and compare each pixel with its right neighbour.
+/// Robust to rescaling and re-encoding; Hamming distance between hashes approximates visual difference.
+pub fn dhash(img: &image::DynamicImage) -> u64 {
+    let small = img.thumbnail_exact(9, 8).to_luma8();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let left = small.get_pixel(x, y)[0];
+            let right = small.get_pixel(x + 1, y)[0];
+            hash = (hash << 1) | u64::from(left < right);
+        }
+    }
+    hash
+}
+
+fn find_root(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
+/// Find near-duplicate images by perceptual hash (dHash). Images within `threshold` bits of each
+/// other (transitively) form a group. Hashing runs in parallel off the main thread.
+#[tauri::command]
+pub async fn find_duplicate_images(
+    payload: FindDuplicateImagesPayload,
+) -> Result<FindDuplicateImagesResult, CommandError> {
+    let root = PathBuf::from(&payload.root_path);
+    if !root.exists() || !root.is_dir() {
+        return Err(CommandError::NotFound("Folder does not exist".to_string()));
+    }
+    let canonical_root = root.canonicalize()?;
+    let threshold = payload.threshold.min(64);
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let image_paths = ProjectWalk::default().images(&canonical_root).images;
+
+        // Undecodable images are left out rather than failing the scan.
+        let hashes: Vec<(String, u64)> = image_paths
+            .par_iter()
+            .filter_map(|path| {
+                let img = open_image(path).ok()?;
+                let rel = path
+                    .strip_prefix(&canonical_root)
+                    .map(|r| normalize_rel(&r.to_string_lossy()))
+                    .ok()?;
+                Some((rel, dhash(&img)))
+            })
+            .collect();
+
+        let mut parent: Vec<usize> = (0..hashes.len()).collect();
+        let mut pairs: Vec<(usize, usize, u32)> = Vec::new();
+        for i in 0..hashes.len() {
+            for j in (i + 1)..hashes.len() {
+                let distance = (hashes[i].1 ^ hashes[j].1).count_ones();
+                if distance <= threshold {
+                    pairs.push((i, j, distance));
+                    let (ri, rj) = (find_root(&mut parent, i), find_root(&mut parent, j));
+                    if ri != rj {
+                        parent[rj] = ri;
+                    }
+                }
+            }
+        }
+
+        let mut by_root: HashMap<usize, SimilarImageGroup> = HashMap::new();
+        for (i, j, distance) in pairs {
+            let group =
+                by_root
+                    .entry(find_root(&mut parent, i))
+                    .or_insert_with(|| SimilarImageGroup {
+                        paths: Vec::new(),
+                        distances: Vec::new(),
+                    });
+            for idx in [i, j] {
+                if !group.paths.contains(&hashes[idx].0) {
+                    group.paths.push(hashes[idx].0.clone());
+                }
+            }
+            group.distances.push(PairDistance {
+                a: hashes[i].0.clone(),
+                b: hashes[j].0.clone(),
+                distance,
+            });
+        }
+
+        let mut groups: Vec<SimilarImageGroup> = by_root.into_values().collect();
+        for group in &mut groups {
+            group.paths.sort();
+        }
+        groups.sort_by(|a, b| a.paths.cmp(&b.paths));
+        FindDuplicateImagesResult { groups }
+    })
+    .await
+    .map_err(CommandError::from)
+}
+
 #[derive(Debug, Deserialize)]
 pub struct LoadImageDimensionsPayload {
     pub paths: Vec<String>,
@@ -263,7 +378,9 @@ pub struct ImageDimensions {
 
 /// Load image dimensions in parallel for a batch of images
 #[tauri::command]
-pub fn load_image_dimensions(payload: LoadImageDimensionsPayload) -> Result<Vec<ImageDimensions>, String> {
+pub fn load_image_dimensions(
+    payload: LoadImageDimensionsPayload,
+) -> Result<Vec<ImageDimensions>, CommandError> {
     let results: Vec<ImageDimensions> = payload
         .paths
         .par_iter()
@@ -273,7 +390,7 @@ pub fn load_image_dimensions(payload: LoadImageDimensionsPayload) -> Result<Vec<
                 .ok()
                 .and_then(|r| r.into_dimensions().ok())
                 .unwrap_or((0u32, 0u32));
-            
+
             ImageDimensions {
                 path: path_str.clone(),
                 width: if width > 0 { Some(width) } else { None },
@@ -284,3 +401,309 @@ pub fn load_image_dimensions(payload: LoadImageDimensionsPayload) -> Result<Vec<
 
     Ok(results)
 }
+
+#[derive(Debug, Deserialize)]
+pub struct FindCorruptImagesPayload {
+    pub root_path: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CorruptImage {
+    pub relative_path: String,
+    pub error: String,
+}
+
+/// Fully decode every image in the project (in parallel, off the main thread) and list the ones
+/// that fail, e.g. truncated downloads, so they can be re-fetched or deleted before training.
+#[tauri::command]
+pub async fn find_corrupt_images(
+    payload: FindCorruptImagesPayload,
+) -> Result<Vec<CorruptImage>, CommandError> {
+    let root = PathBuf::from(&payload.root_path);
+    if !root.is_dir() {
+        return Err(CommandError::NotFound("Folder does not exist".to_string()));
+    }
+    let canonical_root = root.canonicalize()?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let image_paths = ProjectWalk::default().images(&canonical_root).images;
+        let mut corrupt: Vec<CorruptImage> = image_paths
+            .par_iter()
+            .filter_map(|path| {
+                let error = validate_image(path).err()?.to_string();
+                let rel = path.strip_prefix(&canonical_root).unwrap_or(path);
+                Some(CorruptImage {
+                    relative_path: normalize_rel(&rel.to_string_lossy()),
+                    error,
+                })
+            })
+            .collect();
+        corrupt.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+        corrupt
+    })
+    .await
+    .map_err(CommandError::from)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScanProjectPayload {
+    pub root_path: String,
+    /// Descend into symlinked folders. WalkDir detects symlink cycles and skips the looping entry,
+    /// but links can still pull in large trees from outside the project.
+    #[serde(default)]
+    pub follow_symlinks: bool,
+    /// Globs (relative path, case-insensitive) of images to leave out, e.g. `raw/**`. Hidden
+    /// files and folders are always skipped.
+    #[serde(default)]
+    pub ignore_patterns: Option<Vec<String>>,
+    /// Fully decode every image so truncated or corrupt files are flagged. Much slower than the
+    /// default header-only check.
+    #[serde(default)]
+    pub validate: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScannedImage {
+    pub relative_path: String,
+    pub absolute_path: String,
+    pub has_caption: bool,
+    pub tag_count: usize,
+    pub rating: String,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// The header is readable and, with `validate`, the whole image decodes.
+    pub is_valid: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScanProjectResult {
+    pub images: Vec<ScannedImage>,
+    /// Images skipped by the walk as hidden or matching ignore_patterns / `.loraignore`.
+    pub ignored_count: usize,
+    /// `.loraignore` lines that failed to parse and were skipped.
+    pub ignore_warnings: Vec<String>,
+}
+
+/// Scan a project in one call: caption status, tag count, rating and header-only dimensions for
+/// every image (full decode with `validate`). Ratings are loaded once; per-image work runs in
+/// parallel, off the main thread.
+#[tauri::command]
+pub async fn scan_project(payload: ScanProjectPayload) -> Result<ScanProjectResult, CommandError> {
+    let root = PathBuf::from(&payload.root_path);
+    if !root.exists() || !root.is_dir() {
+        return Err(CommandError::NotFound("Folder does not exist".to_string()));
+    }
+    let canonical_root = root.canonicalize()?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let project_root = canonical_root
+            .to_str()
+            .unwrap_or(payload.root_path.as_str())
+            .to_string();
+        let ratings_data = load_ratings(&project_root);
+        let ratings = RatingLookup::new(&ratings_data, &project_root);
+
+        let walker = ProjectWalk::new(payload.follow_symlinks, payload.ignore_patterns.as_deref())?;
+        let walked = walker.images(&canonical_root);
+
+        let mut images: Vec<ScannedImage> = walked
+            .images
+            .par_iter()
+            .map(|path| {
+                let relative_path = path
+                    .strip_prefix(&canonical_root)
+                    .map(|r| normalize_rel(&r.to_string_lossy()))
+                    .unwrap_or_default();
+                let caption = sidecar::read_for_image(path);
+                let tag_count = caption
+                    .as_deref()
+                    .map(|raw| parse_tags(raw).len())
+                    .unwrap_or(0);
+                let rating = ImageRating::from_str(ratings.get(&relative_path, &relative_path));
+                let (mut width, mut height, mut error) = (None, None, None);
+                match image::image_dimensions(path) {
+                    Ok((w, h)) if w > 0 && h > 0 => (width, height) = (Some(w), Some(h)),
+                    Ok(_) => error = Some("Image has zero size".to_string()),
+                    Err(e) => error = Some(e.to_string()),
+                }
+                if payload.validate && error.is_none() {
+                    error = validate_image(path).err().map(|e| e.to_string());
+                }
+                ScannedImage {
+                    relative_path,
+                    absolute_path: path.to_string_lossy().into_owned(),
+                    has_caption: caption.is_some(),
+                    tag_count,
+                    rating: rating.as_str().to_string(),
+                    width,
+                    height,
+                    is_valid: error.is_none(),
+                    error,
+                }
+            })
+            .collect();
+
+        images.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+        Ok(ScanProjectResult {
+            images,
+            ignored_count: walked.ignored,
+            ignore_warnings: walked.warnings,
+        })
+    })
+    .await
+    .map_err(CommandError::from)?
+}
+
+const FILE_CHANGED_EVENT: &str = "project-file-changed";
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Active project watcher. Dropping it stops the OS watch and ends the debounce thread.
+static PROJECT_WATCHER: Lazy<Mutex<Option<RecommendedWatcher>>> = Lazy::new(|| Mutex::new(None));
+
+#[derive(Debug, Deserialize)]
+pub struct WatchProjectPayload {
+    pub root_path: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ProjectFileChanged {
+    kind: &'static str,
+    relative_path: String,
+}
+
+fn change_kind(kind: &EventKind, path: &Path) -> Option<&'static str> {
+    match kind {
+        EventKind::Create(_) => Some("created"),
+        EventKind::Remove(_) => Some("removed"),
+        // Renames arrive as separate from/to paths; report whichever side this path is on.
+        EventKind::Modify(ModifyKind::Name(_)) => {
+            Some(if path.exists() { "created" } else { "removed" })
+        }
+        EventKind::Modify(ModifyKind::Metadata(_)) => None,
+        EventKind::Modify(_) => Some("modified"),
+        _ => None,
+    }
+}
+
+/// Images and caption sidecars of any kind (.txt, .caption, .json) outside hidden folders, so
+/// trash moves and `.lora-studio` / `.caption_history` writes are skipped. A `.json` only counts
+/// when an image with the same stem exists; other JSON files aren't captions.
+fn is_watched_path(root: &Path, path: &Path) -> bool {
+    if is_hidden_under(root, path) {
+        return false;
+    }
+    if is_image_path(path) {
+        return true;
+    }
+    let Some(ext) = path.extension().and_then(|e| e.to_str()) else {
+        return false;
+    };
+    match sidecar::DETECT_ORDER
+        .into_iter()
+        .find(|c| ext.eq_ignore_ascii_case(c.extension()))
+    {
+        Some(CaptionExt::Json) => has_image_sibling(path),
+        Some(_) => true,
+        None => false,
+    }
+}
+
+/// Watch a project folder and emit `project-file-changed` for image and caption changes, skipping
+/// hidden files and folders as the project walk does. Events are coalesced per file over a short
+/// window so editors saving in several steps produce one event. Replaces any previously watched
+/// project.
+#[tauri::command]
+pub fn watch_project(app: AppHandle, payload: WatchProjectPayload) -> Result<(), CommandError> {
+    let root = PathBuf::from(&payload.root_path);
+    if !root.is_dir() {
+        return Err(CommandError::NotFound("Folder does not exist".to_string()));
+    }
+    let canonical_root = root.canonicalize()?;
+
+    let (tx, rx) = mpsc::channel::<notify::Result<Event>>();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    watcher.watch(&canonical_root, RecursiveMode::Recursive)?;
+
+    std::thread::spawn(move || {
+        let mut pending: HashMap<String, &'static str> = HashMap::new();
+        let mut window_start: Option<Instant> = None;
+        loop {
+            match rx.recv_timeout(WATCH_DEBOUNCE) {
+                Ok(Ok(event)) => {
+                    for path in &event.paths {
+                        if !is_watched_path(&canonical_root, path) {
+                            continue;
+                        }
+                        let Some(kind) = change_kind(&event.kind, path) else {
+                            continue;
+                        };
+                        let Ok(rel) = path.strip_prefix(&canonical_root) else {
+                            continue;
+                        };
+                        let rel = normalize_rel(&rel.to_string_lossy());
+                        // A create followed by writes is still a create.
+                        let entry = pending.entry(rel).or_insert(kind);
+                        if !(*entry == "created" && kind == "modified") {
+                            *entry = kind;
+                        }
+                        window_start.get_or_insert_with(Instant::now);
+                    }
+                }
+                Ok(Err(_)) | Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+            if window_start.is_some_and(|t| t.elapsed() >= WATCH_DEBOUNCE) {
+                for (relative_path, kind) in pending.drain() {
+                    let _ = app.emit(
+                        FILE_CHANGED_EVENT,
+                        ProjectFileChanged {
+                            kind,
+                            relative_path,
+                        },
+                    );
+                }
+                window_start = None;
+            }
+        }
+    });
+
+    *PROJECT_WATCHER
+        .lock()
+        .map_err(|e| CommandError::other(e.to_string()))? = Some(watcher);
+    Ok(())
+}
+
+/// Stop watching the current project, if any.
+#[tauri::command]
+pub fn unwatch_project() -> Result<(), CommandError> {
+    PROJECT_WATCHER
+        .lock()
+        .map_err(|e| CommandError::other(e.to_string()))?
+        .take();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watched_paths_skip_hidden_folders_and_unpaired_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::write(root.join("a.png"), b"").unwrap();
+        for (rel, watched) in [
+            ("a.png", true),
+            ("a.txt", true),
+            ("b.caption", true),
+            ("a.json", true),
+            ("settings.json", false),
+            (".lora-studio/ratings.json", false),
+            (".lora-studio/trash/1/a.png", false),
+            (".caption_history/a.json", false),
+            ("sub/.hidden.png", false),
+            ("notes.md", false),
+        ] {
+            assert_eq!(is_watched_path(root, &root.join(rel)), watched, "{}", rel);
+        }
+    }
+}
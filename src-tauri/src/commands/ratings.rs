@@ -1,7 +1,15 @@
+use once_cell::sync::Lazy;
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use super::error::CommandError;
+use super::images::{sharpness_score, DEFAULT_BLUR_THRESHOLD};
+use super::sidecar;
+use super::walk::ProjectWalk;
 
 /// Image rating status.
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
@@ -43,11 +51,54 @@ pub struct RatingsData {
 
 /// Get the ratings file path for a project root.
 fn ratings_file_path(root: &str) -> PathBuf {
-    PathBuf::from(root).join(".lora-studio").join("ratings.json")
+    PathBuf::from(root)
+        .join(".lora-studio")
+        .join("ratings.json")
+}
+
+/// Per-project locks serializing ratings file access, keyed by canonical root (callers pass both
+/// raw and canonical roots for the same project). Process-global rather than `tauri::State`:
+/// load_ratings and rename_rating_keys are plain helpers used by export, scan and rename code
+/// that has no AppHandle, like the other command statics (EXPORT_CANCELLED, PROJECT_WATCHER).
+static RATINGS_LOCKS: Lazy<Mutex<HashMap<PathBuf, Arc<Mutex<()>>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn ratings_lock(root: &str) -> Arc<Mutex<()>> {
+    let key = fs::canonicalize(root).unwrap_or_else(|_| PathBuf::from(root));
+    let mut locks = RATINGS_LOCKS.lock().unwrap_or_else(|e| e.into_inner());
+    locks.entry(key).or_default().clone()
 }
 
 /// Load ratings from file.
 pub fn load_ratings(root: &str) -> RatingsData {
+    let lock = ratings_lock(root);
+    let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+    read_ratings_file(root)
+}
+
+/// Load, modify and save a project's ratings under its lock, so concurrent commands (quick
+/// rating while an export reads) never lose each other's updates.
+fn update_ratings<T>(root: &str, f: impl FnOnce(&mut RatingsData) -> T) -> Result<T, CommandError> {
+    let lock = ratings_lock(root);
+    let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+    let mut data = read_ratings_file(root);
+    let out = f(&mut data);
+    save_ratings(root, &data)?;
+    Ok(out)
+}
+
+/// Move ratings from old to new relative paths after files are renamed. Other ratings are kept.
+pub fn rename_rating_keys(root: &str, mappings: &[(String, String)]) -> Result<(), CommandError> {
+    update_ratings(root, |data| {
+        for (old_path, new_path) in mappings {
+            if let Some(rating) = data.ratings.remove(old_path) {
+                data.ratings.insert(new_path.clone(), rating);
+            }
+        }
+    })
+}
+
+fn read_ratings_file(root: &str) -> RatingsData {
     let path = ratings_file_path(root);
     if !path.exists() {
         return RatingsData::default();
@@ -58,25 +109,131 @@ pub fn load_ratings(root: &str) -> RatingsData {
     }
 }
 
-/// Save ratings to file.
-fn save_ratings(root: &str, data: &RatingsData) -> Result<(), String> {
+/// Save ratings to file. Written to a temp file and renamed over the old one so a crash never
+/// leaves a truncated ratings file. Callers hold the project's ratings lock.
+fn save_ratings(root: &str, data: &RatingsData) -> Result<(), CommandError> {
     let path = ratings_file_path(root);
     if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        fs::create_dir_all(parent)?;
+    }
+    let content = serde_json::to_string_pretty(data)?;
+    let tmp = path.with_extension("json.tmp");
+    if let Err(e) = fs::write(&tmp, content).and_then(|_| fs::rename(&tmp, &path)) {
+        let _ = fs::remove_file(&tmp);
+        return Err(e.into());
     }
-    let content = serde_json::to_string_pretty(data).map_err(|e| e.to_string())?;
-    fs::write(&path, content).map_err(|e| e.to_string())?;
     Ok(())
 }
 
-/// Get rating for a specific image.
-#[allow(dead_code)]
-pub fn get_rating(root: &str, relative_path: &str) -> ImageRating {
-    let data = load_ratings(root);
-    data.ratings
-        .get(relative_path)
-        .map(|s| ImageRating::from_str(s))
-        .unwrap_or(ImageRating::None)
+/// Normalize relative path: forward slashes, trim leading slashes.
+pub fn normalize_rel(s: &str) -> String {
+    s.replace('\\', "/")
+        .trim_start_matches(['/', '\\'])
+        .to_string()
+}
+
+/// Normalize for case-insensitive path comparison (e.g. Windows).
+pub fn normalize_key_for_lookup(s: &str) -> String {
+    normalize_rel(s).to_lowercase()
+}
+
+/// Ratings keys normalized once for reads, so looking up every image in a project is one hash
+/// probe each instead of a pass over all stored keys.
+pub struct RatingLookup<'a> {
+    data: &'a RatingsData,
+    normalized: HashMap<String, &'a str>,
+}
+
+impl<'a> RatingLookup<'a> {
+    pub fn new(data: &'a RatingsData, project_root: &str) -> Self {
+        let root_norm = normalize_key_for_lookup(project_root);
+        let mut normalized = HashMap::new();
+        let mut absolute = Vec::new();
+        for (k, v) in &data.ratings {
+            let k_norm = normalize_key_for_lookup(k);
+            // Keys may have been stored as absolute paths if strip_prefix failed when the project
+            // was opened.
+            if let Some(suffix) = k_norm
+                .strip_prefix(&root_norm)
+                .filter(|_| !root_norm.is_empty())
+            {
+                let suffix = suffix.trim_start_matches(['/', '\\']);
+                if !suffix.is_empty() {
+                    absolute.push((suffix.to_string(), v.as_str()));
+                }
+            }
+            normalized.entry(k_norm).or_insert(v.as_str());
+        }
+        // A relative key wins over an absolute one for the same image.
+        for (k, v) in absolute {
+            normalized.entry(k).or_insert(v);
+        }
+        Self { data, normalized }
+    }
+
+    /// Rating for a relative path: exact key, then case-insensitive, then a key stored as an
+    /// absolute path under the project root. "none" if unrated.
+    pub fn get(&self, rel_key: &str, rel: &str) -> &'a str {
+        let ratings = &self.data.ratings;
+        ratings
+            .get(rel_key)
+            .or_else(|| ratings.get(rel))
+            .map(String::as_str)
+            .or_else(|| {
+                self.normalized
+                    .get(&normalize_key_for_lookup(rel_key))
+                    .copied()
+            })
+            .unwrap_or("none")
+    }
+}
+
+/// Look up the rating of one image; use RatingLookup when looking up many.
+pub fn get_rating_for_path(
+    ratings: &RatingsData,
+    rel_key: &str,
+    rel: &str,
+    project_root: &str,
+) -> String {
+    RatingLookup::new(ratings, project_root)
+        .get(rel_key, rel)
+        .to_string()
+}
+
+/// Ratings keys grouped by normalize_key_for_lookup. Built once per update so bulk changes don't
+/// re-normalize every stored key for each image they touch.
+struct RatingKeys(HashMap<String, Vec<String>>);
+
+impl RatingKeys {
+    fn new(data: &RatingsData) -> Self {
+        let mut keys: HashMap<String, Vec<String>> = HashMap::new();
+        for k in data.ratings.keys() {
+            keys.entry(normalize_key_for_lookup(k))
+                .or_default()
+                .push(k.clone());
+        }
+        Self(keys)
+    }
+
+    /// Set or clear (rating None) the entry for an image. The key is stored normalized, and any
+    /// older key for the same image (backslashes, different case) is dropped so it can't shadow
+    /// it.
+    fn set(&mut self, data: &mut RatingsData, relative_path: &str, rating: ImageRating) {
+        let key = normalize_rel(relative_path);
+        let aliases = self.0.entry(normalize_key_for_lookup(&key)).or_default();
+        for old in aliases.drain(..) {
+            if old != key {
+                data.ratings.remove(&old);
+            }
+        }
+        if rating == ImageRating::None {
+            data.ratings.remove(&key);
+        } else {
+            data.ratings
+                .insert(key.clone(), rating.as_str().to_string());
+            aliases.push(key);
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -88,18 +245,67 @@ pub struct SetRatingPayload {
 
 /// Set rating for an image.
 #[tauri::command]
-pub fn set_rating(payload: SetRatingPayload) -> Result<(), String> {
-    let mut data = load_ratings(&payload.root_path);
-    
+pub fn set_rating(payload: SetRatingPayload) -> Result<(), CommandError> {
     let rating = ImageRating::from_str(&payload.rating);
-    if rating == ImageRating::None {
-        data.ratings.remove(&payload.relative_path);
-    } else {
-        data.ratings.insert(payload.relative_path, rating.as_str().to_string());
+    update_ratings(&payload.root_path, |data| {
+        RatingKeys::new(data).set(data, &payload.relative_path, rating)
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetRatingPayload {
+    pub root_path: String,
+    pub relative_path: String,
+}
+
+/// Get the rating for one image ("none" if unrated).
+#[tauri::command]
+pub fn get_rating(payload: GetRatingPayload) -> Result<String, CommandError> {
+    let data = load_ratings(&payload.root_path);
+    let rel_key = normalize_rel(&payload.relative_path);
+    Ok(get_rating_for_path(
+        &data,
+        &rel_key,
+        &payload.relative_path,
+        &payload.root_path,
+    ))
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct RatingCounts {
+    pub good: usize,
+    pub bad: usize,
+    pub needs_edit: usize,
+    pub none: usize,
+}
+
+/// Count images per rating bucket. Walks the project (honoring `.loraignore`) so unrated images
+/// are counted as "none" and ratings for deleted or ignored files are left out.
+#[tauri::command]
+pub fn get_rating_counts(payload: GetRatingsPayload) -> Result<RatingCounts, CommandError> {
+    let root = PathBuf::from(&payload.root_path);
+    if !root.is_dir() {
+        return Err(CommandError::NotFound("Folder does not exist".to_string()));
     }
-    
-    save_ratings(&payload.root_path, &data)?;
-    Ok(())
+    let canonical = root.canonicalize()?;
+    let project_root = canonical.to_str().unwrap_or(payload.root_path.as_str());
+    let data = load_ratings(project_root);
+
+    let lookup = RatingLookup::new(&data, project_root);
+    let mut counts = RatingCounts::default();
+    for p in ProjectWalk::default().images(&canonical).images {
+        let rel = match p.strip_prefix(&canonical) {
+            Ok(r) => normalize_rel(&r.to_string_lossy()),
+            Err(_) => continue,
+        };
+        match ImageRating::from_str(lookup.get(&rel, &rel)) {
+            ImageRating::Good => counts.good += 1,
+            ImageRating::Bad => counts.bad += 1,
+            ImageRating::NeedsEdit => counts.needs_edit += 1,
+            ImageRating::None => counts.none += 1,
+        }
+    }
+    Ok(counts)
 }
 
 #[derive(Debug, Deserialize)]
@@ -109,23 +315,23 @@ pub struct GetRatingsPayload {
 
 /// Get all ratings for a project.
 #[tauri::command]
-pub fn get_ratings(payload: GetRatingsPayload) -> Result<HashMap<String, String>, String> {
+pub fn get_ratings(payload: GetRatingsPayload) -> Result<HashMap<String, String>, CommandError> {
     let data = load_ratings(&payload.root_path);
     Ok(data.ratings)
 }
 
 /// Clear all ratings for a project.
 #[tauri::command]
-pub fn clear_all_ratings(payload: GetRatingsPayload) -> Result<usize, String> {
+pub fn clear_all_ratings(payload: GetRatingsPayload) -> Result<usize, CommandError> {
     let path = ratings_file_path(&payload.root_path);
     if !path.exists() {
         return Ok(0);
     }
-    let data = load_ratings(&payload.root_path);
-    let count = data.ratings.len();
-    let empty = RatingsData::default();
-    save_ratings(&payload.root_path, &empty)?;
-    Ok(count)
+    update_ratings(&payload.root_path, |data| {
+        let count = data.ratings.len();
+        *data = RatingsData::default();
+        count
+    })
 }
 
 #[derive(Debug, Deserialize)]
@@ -142,18 +348,227 @@ pub struct SetRatingsBatchPayload {
 
 /// Set ratings for multiple images in a single operation (reduces file I/O)
 #[tauri::command]
-pub fn set_ratings_batch(payload: SetRatingsBatchPayload) -> Result<(), String> {
-    let mut data = load_ratings(&payload.root_path);
-    
-    for change in &payload.changes {
-        let rating = ImageRating::from_str(&change.rating);
-        if rating == ImageRating::None {
-            data.ratings.remove(&change.relative_path);
-        } else {
-            data.ratings.insert(change.relative_path.clone(), rating.as_str().to_string());
+pub fn set_ratings_batch(payload: SetRatingsBatchPayload) -> Result<(), CommandError> {
+    update_ratings(&payload.root_path, |data| {
+        let mut keys = RatingKeys::new(data);
+        for change in &payload.changes {
+            let rating = ImageRating::from_str(&change.rating);
+            keys.set(data, &change.relative_path, rating);
+        }
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetRatingsBulkPayload {
+    pub root_path: String,
+    pub relative_paths: Vec<String>,
+    pub rating: String,
+}
+
+/// Apply one rating to many images with a single load/save. Returns the number of images updated.
+#[tauri::command]
+pub fn set_ratings_bulk(payload: SetRatingsBulkPayload) -> Result<usize, CommandError> {
+    let rating = ImageRating::from_str(&payload.rating);
+    update_ratings(&payload.root_path, |data| {
+        let mut keys = RatingKeys::new(data);
+        let mut updated = 0usize;
+        for relative_path in &payload.relative_paths {
+            if normalize_rel(relative_path).is_empty() {
+                continue;
+            }
+            keys.set(data, relative_path, rating);
+            updated += 1;
+        }
+        updated
+    })
+}
+
+/// Heuristics for auto_rate. An image that can't be decoded or is under the minimum resolution is
+/// rated bad; one that is blurry or (with require_caption) uncaptioned needs_edit; the rest good.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct AutoRateRules {
+    /// Minimum shortest side in pixels.
+    pub min_resolution: Option<u32>,
+    /// Sharpness below this is treated as blurry; unset skips the check. See
+    /// assess_sharpness_batch; DEFAULT_BLUR_THRESHOLD is a reasonable start.
+    pub sharpness_threshold: Option<f64>,
+    pub require_caption: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AutoRatePayload {
+    pub root_path: String,
+    #[serde(default)]
+    pub rules: AutoRateRules,
+    /// Also replace ratings that are already set (manual or earlier auto_rate).
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct AutoRateResult {
+    pub good: usize,
+    pub bad: usize,
+    pub needs_edit: usize,
+    /// Already rated and left alone (overwrite not set).
+    pub kept: usize,
+}
+
+fn has_caption(image: &Path) -> bool {
+    sidecar::read_for_image(image).is_some_and(|c| !c.trim().is_empty())
+}
+
+fn auto_rating(image: &Path, rules: &AutoRateRules) -> ImageRating {
+    let Ok((width, height)) = image::image_dimensions(image) else {
+        return ImageRating::Bad;
+    };
+    if rules
+        .min_resolution
+        .is_some_and(|min| width.min(height) < min)
+    {
+        return ImageRating::Bad;
+    }
+    if let Some(threshold) = rules.sharpness_threshold {
+        match sharpness_score(image) {
+            Ok(score) if score < threshold => return ImageRating::NeedsEdit,
+            Ok(_) => {}
+            Err(_) => return ImageRating::Bad,
         }
     }
-    
-    save_ratings(&payload.root_path, &data)?;
-    Ok(())
+    if rules.require_caption && !has_caption(image) {
+        return ImageRating::NeedsEdit;
+    }
+    ImageRating::Good
+}
+
+/// Rate every image in the project from the rules, as a starting point for manual review.
+/// Existing ratings are kept unless overwrite is set. Analysis runs in parallel.
+#[tauri::command]
+pub async fn auto_rate(payload: AutoRatePayload) -> Result<AutoRateResult, CommandError> {
+    let root = PathBuf::from(&payload.root_path);
+    if !root.is_dir() {
+        return Err(CommandError::NotFound("Folder does not exist".to_string()));
+    }
+    if payload
+        .rules
+        .sharpness_threshold
+        .is_some_and(|t| !t.is_finite() || t < 0.0)
+    {
+        return Err(CommandError::invalid(format!(
+            "Invalid sharpness threshold (default is {})",
+            DEFAULT_BLUR_THRESHOLD
+        )));
+    }
+    let canonical = root.canonicalize()?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let project_root = canonical.to_string_lossy().to_string();
+        let existing = load_ratings(&project_root);
+        let lookup = RatingLookup::new(&existing, &project_root);
+        let mut result = AutoRateResult::default();
+        let mut pending: Vec<(String, PathBuf)> = Vec::new();
+        for image in ProjectWalk::default().images(&canonical).images {
+            let Ok(rel) = image.strip_prefix(&canonical) else {
+                continue;
+            };
+            let rel = normalize_rel(&rel.to_string_lossy());
+            if !payload.overwrite
+                && ImageRating::from_str(lookup.get(&rel, &rel)) != ImageRating::None
+            {
+                result.kept += 1;
+            } else {
+                pending.push((rel, image));
+            }
+        }
+        let rated: Vec<(String, ImageRating)> = pending
+            .into_par_iter()
+            .map(|(rel, image)| {
+                let rating = auto_rating(&image, &payload.rules);
+                (rel, rating)
+            })
+            .collect();
+        update_ratings(&project_root, |data| {
+            // A rating set while the analysis ran wins over the heuristic. Each image only
+            // touches its own keys, so checking against the file as loaded here is enough.
+            let lookup = RatingLookup::new(data, &project_root);
+            let (kept, apply): (Vec<_>, Vec<_>) = rated.iter().partition(|(rel, _)| {
+                !payload.overwrite
+                    && ImageRating::from_str(lookup.get(rel, rel)) != ImageRating::None
+            });
+            result.kept += kept.len();
+            let mut keys = RatingKeys::new(data);
+            for (rel, rating) in apply {
+                keys.set(data, rel, *rating);
+                match rating {
+                    ImageRating::Good => result.good += 1,
+                    ImageRating::Bad => result.bad += 1,
+                    ImageRating::NeedsEdit => result.needs_edit += 1,
+                    ImageRating::None => {}
+                }
+            }
+        })?;
+        Ok(result)
+    })
+    .await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(entries: &[(&str, &str)]) -> RatingsData {
+        RatingsData {
+            ratings: entries
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn set_replaces_aliases_of_the_same_image() {
+        let mut ratings = data(&[
+            ("Sub\\A.png", "bad"),
+            ("sub/a.PNG", "good"),
+            ("b.png", "good"),
+        ]);
+        let mut keys = RatingKeys::new(&ratings);
+        keys.set(&mut ratings, "sub/a.png", ImageRating::NeedsEdit);
+        keys.set(&mut ratings, "c.png", ImageRating::Good);
+        assert_eq!(
+            ratings.ratings,
+            data(&[
+                ("sub/a.png", "needs_edit"),
+                ("b.png", "good"),
+                ("c.png", "good")
+            ])
+            .ratings
+        );
+    }
+
+    #[test]
+    fn set_none_clears_every_alias_and_repeated_sets_stay_single() {
+        let mut ratings = data(&[("A.png", "good"), ("a.png", "bad")]);
+        let mut keys = RatingKeys::new(&ratings);
+        keys.set(&mut ratings, "a.png", ImageRating::Good);
+        keys.set(&mut ratings, "A.PNG", ImageRating::Bad);
+        assert_eq!(ratings.ratings, data(&[("A.PNG", "bad")]).ratings);
+        keys.set(&mut ratings, "a.png", ImageRating::None);
+        assert!(ratings.ratings.is_empty());
+    }
+
+    #[test]
+    fn lookup_matches_exact_case_insensitive_and_absolute_keys() {
+        let ratings = data(&[
+            ("a.png", "good"),
+            ("Sub\\B.png", "bad"),
+            ("C:\\Data\\Set\\c.png", "needs_edit"),
+            ("C:/Data/Set/a.png", "bad"),
+        ]);
+        let lookup = RatingLookup::new(&ratings, "C:\\Data\\Set");
+        assert_eq!(lookup.get("a.png", "a.png"), "good");
+        assert_eq!(lookup.get("sub/b.png", "sub/b.png"), "bad");
+        assert_eq!(lookup.get("c.png", "c.png"), "needs_edit");
+        assert_eq!(lookup.get("d.png", "d.png"), "none");
+    }
 }
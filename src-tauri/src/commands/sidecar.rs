@@ -0,0 +1,178 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::captions::parse_tags;
+
+/// Caption sidecar flavour: `<name>.txt`, `<name>.caption` (kohya), or `<name>.json`
+/// holding `{ "caption": "...", "tags": [...] }`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptionExt {
+    #[default]
+    Txt,
+    Caption,
+    Json,
+}
+
+pub const DETECT_ORDER: [CaptionExt; 3] = [CaptionExt::Txt, CaptionExt::Caption, CaptionExt::Json];
+
+impl CaptionExt {
+    pub fn extension(self) -> &'static str {
+        match self {
+            CaptionExt::Txt => "txt",
+            CaptionExt::Caption => "caption",
+            CaptionExt::Json => "json",
+        }
+    }
+
+    fn of(path: &Path) -> CaptionExt {
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        DETECT_ORDER
+            .into_iter()
+            .find(|c| ext.eq_ignore_ascii_case(c.extension()))
+            .unwrap_or_default()
+    }
+}
+
+/// The sidecar that already exists for an image, checked as .txt, .caption, then .json.
+pub fn existing(image: &Path) -> Option<PathBuf> {
+    DETECT_ORDER
+        .into_iter()
+        .map(|ext| image.with_extension(ext.extension()))
+        .find(|p| p.is_file())
+}
+
+/// Every sidecar that exists for an image, e.g. both a .txt and a .json, in detection order.
+pub fn existing_all(image: &Path) -> Vec<PathBuf> {
+    DETECT_ORDER
+        .into_iter()
+        .map(|ext| image.with_extension(ext.extension()))
+        .filter(|p| p.is_file())
+        .collect()
+}
+
+/// Where an image's caption lives: the preferred sidecar if present, else any existing one (so
+/// edits preserve the file's format), else the preferred path for a new file.
+pub fn resolve(image: &Path, preferred: CaptionExt) -> PathBuf {
+    let preferred_path = image.with_extension(preferred.extension());
+    if preferred_path.is_file() {
+        return preferred_path;
+    }
+    existing(image).unwrap_or(preferred_path)
+}
+
+/// Caption text from a sidecar. JSON sidecars yield `caption`, or their `tags` joined when the
+/// caption is empty.
+pub fn read(path: &Path) -> io::Result<String> {
+    let content = fs::read_to_string(path)?;
+    if CaptionExt::of(path) != CaptionExt::Json {
+        return Ok(content);
+    }
+    let value: serde_json::Value = serde_json::from_str(&content)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let Some(obj) = value.as_object() else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "JSON caption sidecar is not an object",
+        ));
+    };
+    let caption = obj.get("caption").and_then(|c| c.as_str()).unwrap_or("");
+    if !caption.trim().is_empty() {
+        return Ok(caption.to_string());
+    }
+    let tags: Vec<&str> = obj
+        .get("tags")
+        .and_then(|t| t.as_array())
+        .map(|a| a.iter().filter_map(|t| t.as_str()).collect())
+        .unwrap_or_default();
+    Ok(tags.join(", "))
+}
+
+/// Contents of a fresh sidecar of the given kind for this caption text.
+pub fn render(ext: CaptionExt, content: &str) -> String {
+    match ext {
+        CaptionExt::Json => {
+            let obj = serde_json::json!({ "caption": content, "tags": parse_tags(content) });
+            serde_json::to_string_pretty(&obj).unwrap_or_default()
+        }
+        _ => content.to_string(),
+    }
+}
+
+/// Write via a temp file in the same directory renamed over the target, so a crash or full disk
+/// leaves either the old file or the new one, never a truncated caption.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy())
+        .unwrap_or_default();
+    let tmp = path.with_file_name(format!(".{}.tmp", name));
+    let result = fs::write(&tmp, contents).and_then(|_| fs::rename(&tmp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// Write caption text to a sidecar (atomically). JSON sidecars get both `caption` and parsed
+/// `tags`; any other keys already in the file are kept.
+pub fn write(path: &Path, content: &str) -> io::Result<()> {
+    if CaptionExt::of(path) != CaptionExt::Json {
+        return write_atomic(path, content.as_bytes());
+    }
+    let mut obj = fs::read_to_string(path)
+        .ok()
+        .and_then(|s| serde_json::from_str::<serde_json::Value>(&s).ok())
+        .and_then(|v| v.as_object().cloned())
+        .unwrap_or_default();
+    obj.insert("caption".to_string(), content.into());
+    obj.insert("tags".to_string(), parse_tags(content).into());
+    let json = serde_json::to_string_pretty(&obj)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    write_atomic(path, json.as_bytes())
+}
+
+/// Read an image's caption from whichever sidecar exists.
+pub fn read_for_image(image: &Path) -> Option<String> {
+    existing(image).and_then(|p| read(&p).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn write_atomic_replaces_whole_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.txt");
+        fs::write(&path, "a much longer original caption, with many tags").unwrap();
+        write_atomic(&path, b"short").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "short");
+        assert_eq!(entries(dir.path()), vec!["a.txt"]);
+    }
+
+    #[test]
+    fn write_atomic_failure_keeps_target_and_removes_temp() {
+        let dir = tempfile::tempdir().unwrap();
+        // A non-empty directory at the target path makes the final rename fail.
+        let path = dir.path().join("a.txt");
+        fs::create_dir(&path).unwrap();
+        fs::write(path.join("inner"), "original").unwrap();
+
+        assert!(write_atomic(&path, b"new caption").is_err());
+        assert!(path.is_dir());
+        assert_eq!(fs::read_to_string(path.join("inner")).unwrap(), "original");
+        assert_eq!(entries(dir.path()), vec!["a.txt"]);
+    }
+}
@@ -0,0 +1,150 @@
+//! Shared project walk: image files under a root, skipping hidden entries (`.git`, `.thumbnails`,
+//! macOS `._` forks), gitignore-style rules from a `.loraignore` at the root, and user ignore
+//! globs matched against the forward-slash relative path.
+
+use glob::{MatchOptions, Pattern};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use super::error::CommandError;
+use super::formats::is_image_path;
+
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: false,
+    require_literal_separator: false,
+    require_literal_leading_dot: false,
+};
+
+#[derive(Default)]
+pub struct ProjectWalk {
+    follow_symlinks: bool,
+    ignore: Vec<Pattern>,
+}
+
+/// Images found by a walk, plus how many image files were left out by the hidden-file rule,
+/// `.loraignore` or an ignore pattern. Hidden folders are pruned without being counted.
+pub struct WalkedImages {
+    pub images: Vec<PathBuf>,
+    pub ignored: usize,
+    /// `.loraignore` lines that didn't parse and were skipped, e.g. "line 3 'a{b': ...".
+    pub warnings: Vec<String>,
+}
+
+/// Gitignore-style rules (e.g. `_rejects/`, `work/**/*.png`, `!keep.png`) applied to every walk.
+pub const IGNORE_FILE_NAME: &str = ".loraignore";
+
+fn is_hidden(name: &std::ffi::OsStr) -> bool {
+    name.to_string_lossy().starts_with('.')
+}
+
+/// True if any component of `path` below `root` is hidden (e.g. `.lora-studio/trash/...`), which
+/// the walk would never reach. Paths outside `root` count as hidden.
+pub fn is_hidden_under(root: &Path, path: &Path) -> bool {
+    let Ok(rel) = path.strip_prefix(root) else {
+        return true;
+    };
+    rel.components().any(|c| is_hidden(c.as_os_str()))
+}
+
+/// Rules from the root's `.loraignore`, read once per walk. Lines that don't parse are skipped
+/// and pushed to `warnings` so one typo doesn't disable the whole file.
+fn load_ignore_file(root: &Path, warnings: &mut Vec<String>) -> Option<Gitignore> {
+    let path = root.join(IGNORE_FILE_NAME);
+    let content = std::fs::read_to_string(&path).ok()?;
+    let mut builder = GitignoreBuilder::new(root);
+    for (i, line) in content.lines().enumerate() {
+        if let Err(e) = builder.add_line(Some(path.clone()), line) {
+            warnings.push(format!("line {} '{}': {}", i + 1, line, e));
+        }
+    }
+    builder.build().ok()
+}
+
+impl ProjectWalk {
+    /// `ignore_patterns` are globs like `raw/**` or `*_mask.png`; an invalid one is an error.
+    pub fn new(
+        follow_symlinks: bool,
+        ignore_patterns: Option<&[String]>,
+    ) -> Result<Self, CommandError> {
+        let ignore = ignore_patterns
+            .unwrap_or_default()
+            .iter()
+            .map(|p| p.trim())
+            .filter(|p| !p.is_empty())
+            .map(|p| {
+                Pattern::new(p).map_err(|e| {
+                    CommandError::invalid(format!("Invalid ignore pattern '{}': {}", p, e))
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            follow_symlinks,
+            ignore,
+        })
+    }
+
+    fn is_ignored(&self, root: &Path, path: &Path, ignore_file: Option<&Gitignore>) -> bool {
+        if path.file_name().is_some_and(is_hidden) {
+            return true;
+        }
+        let Ok(rel) = path.strip_prefix(root) else {
+            return false;
+        };
+        if ignore_file.is_some_and(|g| g.matched_path_or_any_parents(rel, false).is_ignore()) {
+            return true;
+        }
+        let rel = rel.to_string_lossy().replace('\\', "/");
+        self.ignore
+            .iter()
+            .any(|p| p.matches_with(&rel, MATCH_OPTIONS))
+    }
+
+    /// Image files under `root` in walk order.
+    pub fn images(&self, root: &Path) -> WalkedImages {
+        let mut result = WalkedImages {
+            images: Vec::new(),
+            ignored: 0,
+            warnings: Vec::new(),
+        };
+        let ignore_file = load_ignore_file(root, &mut result.warnings);
+        let walk = WalkDir::new(root)
+            .follow_links(self.follow_symlinks)
+            .into_iter()
+            .filter_entry(|e| {
+                e.depth() == 0 || !e.file_type().is_dir() || !is_hidden(e.file_name())
+            });
+        for entry in walk.filter_map(Result::ok) {
+            let path = entry.path();
+            if !path.is_file() || !is_image_path(path) {
+                continue;
+            }
+            if self.is_ignored(root, path, ignore_file.as_ref()) {
+                result.ignored += 1;
+            } else {
+                result.images.push(entry.into_path());
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bad_ignore_lines_are_reported_and_the_rest_still_apply() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::write(root.join(IGNORE_FILE_NAME), "skip.png\nbad{\n").unwrap();
+        for name in ["keep.png", "skip.png"] {
+            std::fs::write(root.join(name), b"").unwrap();
+        }
+        let walked = ProjectWalk::default().images(root);
+        assert_eq!(walked.images, vec![root.join("keep.png")]);
+        assert_eq!(walked.ignored, 1);
+        assert_eq!(walked.warnings.len(), 1);
+        assert!(walked.warnings[0].starts_with("line 2 'bad{'"));
+    }
+}
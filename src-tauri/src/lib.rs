@@ -10,30 +10,75 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             commands::project::open_project,
             commands::project::find_duplicates,
+            commands::project::find_duplicate_images,
+            commands::project::find_corrupt_images,
             commands::project::load_image_dimensions,
+            commands::project::scan_project,
+            commands::project::watch_project,
+            commands::project::unwatch_project,
+            commands::formats::set_image_extensions,
+            commands::formats::get_image_extensions,
             commands::images::get_thumbnail,
+            commands::images::get_image_info,
             commands::images::get_thumbnails_batch,
             commands::images::get_image_data_url,
             commands::images::crop_image,
             commands::images::multi_crop,
+            commands::images::make_square,
+            commands::images::autocrop_borders,
+            commands::images::rotate_image,
+            commands::images::flip_image,
+            commands::images::rotate_image_90,
+            commands::images::restore_original,
+            commands::images::adjust_image,
+            commands::images::get_image_histogram,
+            commands::images::assess_sharpness_batch,
             commands::images::batch_resize,
             commands::images::delete_image,
+            commands::images::move_image,
+            commands::images::copy_image,
+            commands::images::delete_images_bulk,
+            commands::images::restore_trashed,
             commands::captions::read_caption,
+            commands::captions::get_caption_raw,
+            commands::captions::caption_changed_since,
+            commands::captions::can_write_caption,
             commands::captions::get_captions_batch,
+            commands::captions::read_captions_batch,
             commands::captions::write_caption,
             commands::captions::add_tag,
             commands::captions::remove_tag,
             commands::captions::reorder_tags,
+            commands::captions::undo_caption,
+            commands::captions::redo_caption,
+            commands::captions::apply_template,
             commands::captions::clear_all_captions,
+            commands::captions::cancel_clear_captions,
+            commands::captions::apply_blacklist,
+            commands::captions::count_tokens,
+            commands::captions::count_tokens_batch,
+            commands::captions::normalize_tag_separators,
+            commands::captions::caption_coverage,
+            commands::captions::search_captions,
+            commands::captions::rename_tag,
+            commands::captions::suggest_tag_merges,
+            commands::captions::import_captions,
             commands::lm_studio::test_lm_studio_connection,
             commands::lm_studio::generate_caption_lm_studio,
+            commands::caption_provider::generate_caption,
             commands::lm_studio::generate_captions_batch,
             commands::ollama::test_ollama_connection,
+            commands::ollama::pull_ollama_model,
             commands::export::export_dataset,
             commands::export::export_by_rating,
+            commands::export::cancel_export,
             commands::ratings::set_rating,
             commands::ratings::set_ratings_batch,
+            commands::ratings::set_ratings_bulk,
+            commands::ratings::auto_rate,
+            commands::ratings::get_rating,
             commands::ratings::get_ratings,
+            commands::ratings::get_rating_counts,
             commands::ratings::clear_all_ratings,
             commands::crop_status::set_crop_status,
             commands::crop_status::get_crop_statuses,
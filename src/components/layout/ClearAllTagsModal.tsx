@@ -4,6 +4,7 @@ import { Eraser, X, Loader2 } from "lucide-react";
 import { useProjectStore } from "@/stores/projectStore";
 import { useProjectImages } from "@/hooks/useProject";
 import { useSettingsStore } from "@/stores/settingsStore";
+import { useUiStore } from "@/stores/uiStore";
 import { useFocusTrap } from "@/hooks/useFocusTrap";
 import { clearAllCaptions } from "@/lib/tauri";
 
@@ -22,16 +23,22 @@ export function ClearAllTagsModal({ isOpen, onClose }: ClearAllTagsModalProps) {
   const { data: images = [] } = useProjectImages();
   const queryClient = useQueryClient();
   const setPreviousTriggerWord = useSettingsStore((s) => s.setPreviousTriggerWord);
+  const showToast = useUiStore((s) => s.showToast);
 
   const clearAllMutation = useMutation({
     mutationFn: async () => {
       if (!rootPath) throw new Error("No project open.");
       return clearAllCaptions(rootPath);
     },
-    onSuccess: () => {
+    onSuccess: (result) => {
       if (rootPath) {
         queryClient.invalidateQueries({ queryKey: ["project", "images", rootPath] });
       }
+      if (result.failed.length > 0) {
+        showToast(
+          `Could not clear ${result.failed.length} caption file(s), e.g. ${result.failed[0].path}: ${result.failed[0].error}`
+        );
+      }
       setPreviousTriggerWord("");
       setConfirmText("");
       onClose();
@@ -1,4 +1,4 @@
-import { invoke } from "@tauri-apps/api/core";
+import { invoke as tauriInvoke, type InvokeArgs } from "@tauri-apps/api/core";
 import { open } from "@tauri-apps/plugin-dialog";
 import type {
   ImageEntry,
@@ -26,6 +26,43 @@ import type {
  * - No args: get_resource_stats.
  */
 
+export type CommandErrorCode =
+  | "not_found"
+  | "permission_denied"
+  | "not_image"
+  | "invalid_input"
+  | "io"
+  | "decode"
+  | "network"
+  | "timeout"
+  | "subprocess"
+  | "other";
+
+/** Error thrown by every command wrapper; branch on `code` rather than the message text. */
+export class CommandError extends Error {
+  constructor(
+    public readonly code: CommandErrorCode,
+    message: string
+  ) {
+    super(message);
+    this.name = "CommandError";
+  }
+}
+
+function isCommandErrorPayload(err: unknown): err is { code: CommandErrorCode; message: string } {
+  return typeof err === "object" && err !== null && "code" in err && "message" in err;
+}
+
+/** Backend commands reject with `{ code, message }`; rethrow that as a CommandError. */
+async function invoke<T>(cmd: string, args?: InvokeArgs): Promise<T> {
+  try {
+    return await tauriInvoke<T>(cmd, args);
+  } catch (err) {
+    if (isCommandErrorPayload(err)) throw new CommandError(err.code, err.message);
+    throw err;
+  }
+}
+
 export async function openFolder(): Promise<string | null> {
   const selected = await open({
     directory: true,
@@ -64,13 +101,20 @@ export async function findDuplicates(rootPath: string): Promise<FindDuplicatesRe
   });
 }
 
+export interface Thumbnail {
+  data_url: string;
+  width: number;
+  height: number;
+}
+
 export async function getThumbnailDataUrl(
   path: string,
   size?: number
 ): Promise<string> {
-  return invoke<string>("get_thumbnail", {
+  const thumb = await invoke<Thumbnail>("get_thumbnail", {
     payload: { path, size },
   });
+  return thumb.data_url;
 }
 
 export interface ThumbnailResult {
@@ -108,13 +152,33 @@ export interface CropImagePayload {
   save_as_new?: boolean;
   /** If set, resize output to this size (square) for LoRA/training (e.g. 512 or 1024). */
   output_size?: number | null;
+  /** When overwriting in place, keep the original as `<file>.bak`. */
+  auto_backup?: boolean;
+  /** With save_as_new, copy the source caption to the new image (default true). */
+  copy_caption?: boolean;
+  /** Size of the preview the rectangle was drawn on; lets the backend undo EXIF orientation. */
+  display_width?: number;
+  display_height?: number;
+  /** Shrink the rectangle to this [width, height] ratio, keeping its origin. */
+  lock_aspect?: [number, number];
+}
+
+export interface CropImageResult {
+  /** New file path when save_as_new is true. */
+  new_path: string | null;
+  caption_copied: boolean;
+  /** Rectangle actually cropped (after clamping and lock_aspect). */
+  bounds: { x: number; y: number; width: number; height: number };
+  /** Size of the written image after crop, flip, rotate and output_size. */
+  width: number;
+  height: number;
 }
 
-/** Crops image. Returns new path when save_as_new is true, else undefined. */
+/** Crops image. Result carries the new path when save_as_new is true. */
 export async function cropImage(
   payload: CropImagePayload
-): Promise<string | undefined> {
-  return invoke<string | undefined>("crop_image", { payload });
+): Promise<CropImageResult> {
+  return invoke<CropImageResult>("crop_image", { payload });
 }
 
 /** Deletes an image file and its caption .txt from disk. */
@@ -184,6 +248,12 @@ export async function reorderTags(
 
 export interface ClearAllCaptionsResult {
   cleared_count: number;
+  /** Caption files that could not be written; the rest were still cleared. */
+  failed: { path: string; error: string }[];
+  /** Images skipped as hidden or matching ignore patterns. */
+  ignored_count: number;
+  /** Stopped early by cancel_clear_captions; cleared_count is partial. */
+  cancelled: boolean;
 }
 
 export async function clearAllCaptions(
@@ -33,6 +33,11 @@ export interface CaptionData {
   exists: boolean;
   raw: string;
   tags: string[];
+  char_count: number;
+  word_count: number;
+  /** Parsed tag count; a prose caption counts as one tag. */
+  tag_count: number;
+  longest_tag: string | null;
 }
 
 /** Project settings and root. */
@@ -73,9 +78,18 @@ export type AiProvider = "lm_studio" | "ollama";
 export interface ConnectionStatus {
   connected: boolean;
   models: string[];
+  /** Ollama only: models with size and quantization. */
+  models_detailed?: OllamaModel[];
   error: string | null;
 }
 
+export interface OllamaModel {
+  name: string;
+  size_bytes: number | null;
+  quantization: string | null;
+  family: string | null;
+}
+
 /** Caption result from AI. */
 export interface CaptionResult {
   success: boolean;
@@ -164,12 +178,16 @@ export const DEFAULT_EXTRA_OPTIONS: ExtraOption[] = [
 export interface ExportOptions {
   source_path: string;
   dest_path: string;
+  /** Resolve dest_path against the source folder (e.g. "../export"). */
+  dest_relative_to_source?: boolean;
   as_zip: boolean;
   only_captioned: boolean;
   /** If set, only export these relative paths. */
   relative_paths?: string[] | null;
   trigger_word: string | null;
   sequential_naming: boolean;
+  /** Skip byte-identical images after the first (reported as skipped with reason "duplicate"). */
+  dedup_exact?: boolean;
 }
 
 /** Export into good/bad/needs_edit subfolders. */
@@ -178,6 +196,11 @@ export interface ExportByRatingOptions {
   dest_path: string;
   trigger_word?: string | null;
   sequential_naming?: boolean;
+  only_captioned?: boolean;
+  /** Downscale so the longest side is at most this many pixels. */
+  max_side?: number | null;
+  /** Re-encode to "png", "jpeg" or "webp". */
+  convert_format?: string | null;
 }
 
 /** Export result. */
@@ -185,8 +208,14 @@ export interface ExportResult {
   success: boolean;
   exported_count: number;
   skipped_count: number;
+  /** Images left out of the export and why; skipped_count is its length. */
+  skipped?: { path: string; reason: string }[];
   error: string | null;
   output_path: string;
+  /** Export by rating only: totals per bucket (good, bad, needs_edit). */
+  buckets?: { name: string; exported: number; skipped: number }[];
+  /** Images skipped by dedup_exact. */
+  duplicate_count?: number;
 }
 
 /** Batch rename options. */